@@ -1,12 +1,12 @@
 pub mod admin;
 
 use jsonrpsee::proc_macros::rpc;
-use jsonrpsee::types::error::CALL_EXECUTION_FAILED_CODE;
 use jsonrpsee::types::ErrorObjectOwned;
 use rome_sdk::rome_evm_client::{
     error::RomeEvmError,
     indexer::{ProducedBlocks, ProducerParams},
 };
+use rpc_error::{error_object, RESOURCE_UNAVAILABLE_CODE};
 use solana_sdk::clock::Slot;
 use thiserror::Error;
 
@@ -26,13 +26,13 @@ impl From<ApiError> for ErrorObjectOwned {
     fn from(e: ApiError) -> ErrorObjectOwned {
         match e {
             ApiError::ResponseFailed(e) => e,
-            ApiError::RomeEvmError(RomeEvmError::EmulationRevert(mes, data)) => {
-                ErrorObjectOwned::owned(3, mes, Some(data))
-            }
-            ApiError::RomeEvmError(RomeEvmError::EmulationError(err)) => {
-                ErrorObjectOwned::owned(3, err, None::<String>)
-            }
-            _ => ErrorObjectOwned::borrowed(CALL_EXECUTION_FAILED_CODE, "", None),
+            ApiError::RomeEvmError(err) => rpc_error::rome_evm_error(err),
+            ApiError::Hercules(mes) => error_object(
+                RESOURCE_UNAVAILABLE_CODE,
+                mes,
+                "hercules",
+                "hercules_unavailable",
+            ),
         }
     }
 }
@@ -0,0 +1,42 @@
+use crate::api::{AdminServer, ApiResult};
+use crate::solana_providers::{ProviderHealth, SolanaProviders};
+use anyhow::Context;
+use async_trait::async_trait;
+use jsonrpsee::server::{ServerBuilder, ServerHandle};
+use jsonrpsee::RpcModule;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+pub struct ProxyAdmin {
+    providers: Arc<SolanaProviders>,
+}
+
+impl ProxyAdmin {
+    pub fn new(providers: Arc<SolanaProviders>) -> Self {
+        Self { providers }
+    }
+}
+
+#[async_trait]
+impl AdminServer for Arc<ProxyAdmin> {
+    async fn solana_providers(&self) -> ApiResult<Vec<ProviderHealth>> {
+        Ok(self.providers.health())
+    }
+}
+
+pub async fn start_rpc_server(
+    server: Arc<ProxyAdmin>,
+    host: SocketAddr,
+) -> anyhow::Result<ServerHandle> {
+    tracing::info!("Starting the admin RPC server at {host}");
+
+    let rpc = ServerBuilder::default()
+        .build(host)
+        .await
+        .context("Unable to start the admin RPC server")?;
+
+    let mut module = RpcModule::new(());
+    module.merge(AdminServer::into_rpc(server))?;
+
+    Ok(rpc.start(module))
+}
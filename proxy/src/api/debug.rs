@@ -0,0 +1,94 @@
+use {
+    super::DebugServer,
+    crate::{
+        api::{ApiError, ApiResult},
+        filters::get_block,
+        proxy::Proxy,
+        tracer::{trace_call, ReplayBlock},
+    },
+    alloy_rpc_types_trace::geth::{
+        GethDebugTracingCallOptions, GethDebugTracingOptions, GethTrace,
+    },
+    async_trait::async_trait,
+    ethers::types::{BlockId, BlockNumber, TransactionRequest, H256},
+};
+
+#[async_trait]
+impl DebugServer for Proxy {
+    /// Rome-EVM keeps the latest state only, so just pending transactions can be replayed.
+    /// The state a mined transaction was executed on is gone and it is rejected as unsupported.
+    #[tracing::instrument(name = "proxy::debug_trace_transaction", skip(self), fields(tx_hash = ?tx_hash))]
+    async fn debug_trace_transaction(
+        &self,
+        tx_hash: H256,
+        options: Option<GethDebugTracingOptions>,
+    ) -> ApiResult<GethTrace> {
+        let tx = self
+            .rome_evm_client
+            .get_transaction(&tx_hash)
+            .await?
+            .ok_or(ApiError::TransactionNotFound)?;
+
+        if let Some(number) = tx.block_number {
+            return Err(ApiError::Unsupported(format!(
+                "transaction is mined in block {number}, tracing mined transactions requires historical state"
+            )));
+        }
+
+        let mut call = TransactionRequest::new()
+            .from(tx.from)
+            .value(tx.value)
+            .data(tx.input)
+            .gas(tx.gas);
+        if let Some(to) = tx.to {
+            call = call.to(to);
+        }
+        if let Some(gas_price) = tx.gas_price {
+            call = call.gas_price(gas_price);
+        }
+
+        let result = self.trace(None, &call, options.unwrap_or_default()).await;
+        tracing::info!("debug_trace_transaction: {:?}", result);
+        result
+    }
+
+    #[tracing::instrument(name = "proxy::debug_trace_call", skip(self))]
+    async fn debug_trace_call(
+        &self,
+        call: TransactionRequest,
+        block: Option<BlockId>,
+        options: Option<GethDebugTracingCallOptions>,
+    ) -> ApiResult<GethTrace> {
+        let options = options.unwrap_or_default();
+        if options.state_overrides.is_some() || options.block_overrides.is_some() {
+            return Err(ApiError::Unsupported(
+                "state and block overrides are not supported".to_string(),
+            ));
+        }
+
+        let result = self.trace(block, &call, options.tracing_options).await;
+        tracing::info!("debug_trace_call: {:?}", result);
+        result
+    }
+}
+
+impl Proxy {
+    /// Replay the call on the state selected by the block, in the context of the latest block
+    async fn trace(
+        &self,
+        block: Option<BlockId>,
+        call: &TransactionRequest,
+        options: GethDebugTracingOptions,
+    ) -> ApiResult<GethTrace> {
+        let client = self.state.resolve(block).await?;
+        let latest = get_block(&self.rome_evm_client, BlockId::Number(BlockNumber::Latest))
+            .await?
+            .ok_or(ApiError::BlockNotFound)?;
+        let block = ReplayBlock {
+            number: latest.number.unwrap_or_default().as_u64(),
+            timestamp: latest.timestamp.as_u64(),
+        };
+
+        trace_call(client, block, call, options)
+    }
+}
@@ -1,4 +1,8 @@
-use crate::api::{B58Pubkey, EmulationAccountMeta};
+use crate::api::{B58Pubkey, EmulationAccountMeta, FilterChanges};
+use crate::filters::matches_filter;
+use crate::subscriptions::{block_header, pipe_to_sink, SubscriptionKind};
+use jsonrpsee::core::SubscriptionResult;
+use jsonrpsee::PendingSubscriptionSink;
 use {
     super::EthServer,
     crate::{
@@ -7,8 +11,8 @@ use {
     },
     async_trait::async_trait,
     ethers::types::{
-        Address, BlockId, Bytes, FeeHistory, Transaction, TransactionReceipt, TransactionRequest,
-        TxHash, H256, U256, U64,
+        Address, BlockId, Bytes, FeeHistory, Filter, Log, Transaction, TransactionReceipt,
+        TransactionRequest, TxHash, H256, U256, U64,
     },
     rome_sdk::rome_evm_client::indexer::BlockType,
 };
@@ -33,10 +37,12 @@ impl EthServer for Proxy {
         result
     }
 
-    #[tracing::instrument(name = "proxy::eth_get_balance", skip(self), fields(address = ?address))]
-    async fn eth_get_balance(&self, address: Address, _block: String) -> ApiResult<U256> {
+    #[tracing::instrument(name = "proxy::eth_get_balance", skip(self), fields(address = ?address, block = ?block))]
+    async fn eth_get_balance(&self, address: Address, block: Option<BlockId>) -> ApiResult<U256> {
         let result = self
-            .rome_evm_client
+            .state
+            .resolve(block)
+            .await?
             .get_balance(address)
             .map_err(ApiError::RomeEvmError)?;
 
@@ -85,16 +91,27 @@ impl EthServer for Proxy {
     }
 
     #[tracing::instrument(name = "proxy::eth_call", skip(self))]
-    async fn eth_call(&self, call: TransactionRequest, _block: String) -> ApiResult<Bytes> {
-        let result = self.rome_evm_client.call(&call).map_err(|e| e.into());
+    async fn eth_call(&self, call: TransactionRequest, block: Option<BlockId>) -> ApiResult<Bytes> {
+        let result = self
+            .state
+            .resolve(block)
+            .await?
+            .call(&call)
+            .map_err(|e| e.into());
         tracing::info!("eth_call: {:?}", result);
         result
     }
 
-    #[tracing::instrument(name = "proxy::eth_get_transaction_count", skip(self), fields(address = ?address))]
-    async fn eth_get_transaction_count(&self, address: Address, _block: String) -> ApiResult<U64> {
+    #[tracing::instrument(name = "proxy::eth_get_transaction_count", skip(self), fields(address = ?address, block = ?block))]
+    async fn eth_get_transaction_count(
+        &self,
+        address: Address,
+        block: Option<BlockId>,
+    ) -> ApiResult<U64> {
         let result = self
-            .rome_evm_client
+            .state
+            .resolve(block)
+            .await?
             .transaction_count(address)
             .map_err(ApiError::RomeEvmError);
         tracing::info!("eth_get_transaction_count: {:?}, {:?}", address, result);
@@ -111,10 +128,12 @@ impl EthServer for Proxy {
         result
     }
 
-    #[tracing::instrument(name = "proxy::eth_get_code", skip(self), fields(address = ?address))]
-    async fn eth_get_code(&self, address: Address, _block: String) -> ApiResult<Bytes> {
+    #[tracing::instrument(name = "proxy::eth_get_code", skip(self), fields(address = ?address, block = ?block))]
+    async fn eth_get_code(&self, address: Address, block: Option<BlockId>) -> ApiResult<Bytes> {
         let result = self
-            .rome_evm_client
+            .state
+            .resolve(block)
+            .await?
             .get_code(address)
             .map_err(ApiError::RomeEvmError);
         tracing::info!("eth_get_code: {:?} {:?}", address, result);
@@ -129,6 +148,10 @@ impl EthServer for Proxy {
             .await
             .map_err(|e| e.into());
 
+        if let Ok(tx_hash) = &result {
+            self.subscriptions.notify_pending_tx(*tx_hash);
+        }
+
         tracing::info!("eth_send_raw_transaction: {:?}", result);
         result
     }
@@ -200,15 +223,17 @@ impl EthServer for Proxy {
         Ok("proxy-version".to_string())
     }
 
-    #[tracing::instrument(name = "proxy::eth_get_storage_at", skip(self, slot, _block), fields(address = ?address))]
+    #[tracing::instrument(name = "proxy::eth_get_storage_at", skip(self, slot), fields(address = ?address, block = ?block))]
     async fn eth_get_storage_at(
         &self,
         address: Address,
         slot: U256,
-        _block: String,
+        block: Option<BlockId>,
     ) -> ApiResult<String> {
         let value = self
-            .rome_evm_client
+            .state
+            .resolve(block)
+            .await?
             .eth_get_storage_at(address, slot)
             .map_err(ApiError::from)?;
         let mut buf = [0_u8; 32];
@@ -232,4 +257,80 @@ impl EthServer for Proxy {
             .inspect_err(|err| tracing::warn!("emulate_tx error: {:?}", err))?;
         Ok(())
     }
+
+    #[tracing::instrument(name = "proxy::eth_get_logs", skip(self))]
+    async fn eth_get_logs(&self, filter: Filter) -> ApiResult<Vec<Log>> {
+        let result = self.filters.get_logs(&self.rome_evm_client, &filter).await;
+        tracing::info!("eth_get_logs: {:?}", result.as_ref().map(Vec::len));
+        result
+    }
+
+    #[tracing::instrument(name = "proxy::eth_new_filter", skip(self))]
+    async fn eth_new_filter(&self, filter: Filter) -> ApiResult<U256> {
+        let result = self
+            .filters
+            .new_log_filter(&self.rome_evm_client, filter)
+            .await;
+        tracing::info!("eth_new_filter: {:?}", result);
+        result
+    }
+
+    #[tracing::instrument(name = "proxy::eth_new_block_filter", skip(self))]
+    async fn eth_new_block_filter(&self) -> ApiResult<U256> {
+        let result = self.filters.new_block_filter(&self.rome_evm_client).await;
+        tracing::info!("eth_new_block_filter: {:?}", result);
+        result
+    }
+
+    #[tracing::instrument(name = "proxy::eth_get_filter_changes", skip(self))]
+    async fn eth_get_filter_changes(&self, id: U256) -> ApiResult<FilterChanges> {
+        self.filters.changes(&self.rome_evm_client, id).await
+    }
+
+    #[tracing::instrument(name = "proxy::eth_get_filter_logs", skip(self))]
+    async fn eth_get_filter_logs(&self, id: U256) -> ApiResult<Vec<Log>> {
+        self.filters.filter_logs(&self.rome_evm_client, id).await
+    }
+
+    #[tracing::instrument(name = "proxy::eth_uninstall_filter", skip(self))]
+    async fn eth_uninstall_filter(&self, id: U256) -> ApiResult<bool> {
+        let result = self.filters.uninstall(id).await;
+        tracing::info!("eth_uninstall_filter: {:?} {}", id, result);
+        Ok(result)
+    }
+
+    #[tracing::instrument(name = "proxy::eth_subscribe", skip(self, pending))]
+    async fn eth_subscribe(
+        &self,
+        pending: PendingSubscriptionSink,
+        kind: SubscriptionKind,
+        filter: Option<Filter>,
+    ) -> SubscriptionResult {
+        match kind {
+            SubscriptionKind::NewHeads => {
+                pipe_to_sink(pending, self.subscriptions.subscribe_heads(), |block| {
+                    vec![block_header(&block)]
+                })
+                .await
+            }
+            SubscriptionKind::Logs => {
+                let filter = filter.unwrap_or_default();
+                pipe_to_sink(pending, self.subscriptions.subscribe_logs(), |logs| {
+                    logs.iter()
+                        .filter(|log| matches_filter(&filter, log))
+                        .cloned()
+                        .collect()
+                })
+                .await
+            }
+            SubscriptionKind::NewPendingTransactions => {
+                pipe_to_sink(
+                    pending,
+                    self.subscriptions.subscribe_pending_txs(),
+                    |tx_hash| vec![tx_hash],
+                )
+                .await
+            }
+        }
+    }
 }
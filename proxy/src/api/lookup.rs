@@ -0,0 +1,207 @@
+use {
+    super::EthLookupServer,
+    crate::{
+        api::{ApiResult, BlockNumberOrHash},
+        chain::Chain,
+    },
+    async_trait::async_trait,
+    ethers::types::{Address, BlockId, Transaction, TransactionReceipt, H256, U64},
+    std::sync::Arc,
+};
+
+/// Block and transaction lookups of the Ethereum JSON-RPC spec
+pub struct Lookup<C> {
+    chain: Arc<C>,
+}
+
+impl<C: Chain> Lookup<C> {
+    pub fn new(chain: Arc<C>) -> Self {
+        Self { chain }
+    }
+
+    async fn transaction_by_block_and_index(
+        &self,
+        block_id: BlockId,
+        index: U64,
+    ) -> ApiResult<Option<Transaction>> {
+        let Some(block) = self.chain.block(block_id).await? else {
+            return Ok(None);
+        };
+
+        let Some(tx_hash) = block.transactions.get(index.as_usize()) else {
+            return Ok(None);
+        };
+
+        self.chain.transaction(tx_hash).await
+    }
+}
+
+#[async_trait]
+impl<C: Chain> EthLookupServer for Lookup<C> {
+    #[tracing::instrument(name = "proxy::eth_get_block_transaction_count_by_number", skip(self), fields(block_number = ?block_number))]
+    async fn eth_get_block_transaction_count_by_number(
+        &self,
+        block_number: BlockId,
+    ) -> ApiResult<Option<U64>> {
+        let block = self.chain.block(block_number).await?;
+        Ok(block.map(|block| block.transactions.len().into()))
+    }
+
+    #[tracing::instrument(name = "proxy::eth_get_block_transaction_count_by_hash", skip(self), fields(block_hash = %block_hash))]
+    async fn eth_get_block_transaction_count_by_hash(
+        &self,
+        block_hash: H256,
+    ) -> ApiResult<Option<U64>> {
+        let block = self.chain.block(BlockId::Hash(block_hash)).await?;
+        Ok(block.map(|block| block.transactions.len().into()))
+    }
+
+    #[tracing::instrument(name = "proxy::eth_get_transaction_by_block_number_and_index", skip(self), fields(block_number = ?block_number))]
+    async fn eth_get_transaction_by_block_number_and_index(
+        &self,
+        block_number: BlockId,
+        index: U64,
+    ) -> ApiResult<Option<Transaction>> {
+        self.transaction_by_block_and_index(block_number, index)
+            .await
+    }
+
+    #[tracing::instrument(name = "proxy::eth_get_transaction_by_block_hash_and_index", skip(self), fields(block_hash = %block_hash))]
+    async fn eth_get_transaction_by_block_hash_and_index(
+        &self,
+        block_hash: H256,
+        index: U64,
+    ) -> ApiResult<Option<Transaction>> {
+        self.transaction_by_block_and_index(BlockId::Hash(block_hash), index)
+            .await
+    }
+
+    #[tracing::instrument(name = "proxy::eth_get_block_receipts", skip(self), fields(block = ?block))]
+    async fn eth_get_block_receipts(
+        &self,
+        block: BlockNumberOrHash,
+    ) -> ApiResult<Option<Vec<TransactionReceipt>>> {
+        let Some(block) = self.chain.block(block.0).await? else {
+            return Ok(None);
+        };
+
+        let mut receipts = Vec::with_capacity(block.transactions.len());
+        for tx_hash in &block.transactions {
+            if let Some(receipt) = self.chain.receipt(tx_hash).await? {
+                receipts.push(receipt);
+            }
+        }
+
+        Ok(Some(receipts))
+    }
+
+    #[tracing::instrument(name = "proxy::eth_get_uncle_count_by_block_number", skip(self), fields(block_number = ?block_number))]
+    async fn eth_get_uncle_count_by_block_number(
+        &self,
+        block_number: BlockId,
+    ) -> ApiResult<Option<U64>> {
+        // Rome-EVM blocks never have uncles
+        let block = self.chain.block(block_number).await?;
+        Ok(block.map(|_| U64::zero()))
+    }
+
+    #[tracing::instrument(name = "proxy::eth_syncing", skip(self))]
+    async fn eth_syncing(&self) -> ApiResult<bool> {
+        // Blocks are served from the Ethereum block storage filled by the indexer,
+        // the proxy has no chain of its own to sync, so it never reports a sync in progress
+        Ok(false)
+    }
+
+    #[tracing::instrument(name = "proxy::eth_accounts", skip(self))]
+    async fn eth_accounts(&self) -> ApiResult<Vec<Address>> {
+        Ok(vec![])
+    }
+
+    #[tracing::instrument(name = "proxy::net_listening", skip(self))]
+    async fn net_listening(&self) -> ApiResult<bool> {
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "proxy::net_peer_count", skip(self))]
+    async fn net_peer_count(&self) -> ApiResult<U64> {
+        // The proxy talks to Solana RPC nodes and does not join an Ethereum p2p network
+        Ok(U64::zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::MockChain;
+    use std::path::{Path, PathBuf};
+
+    fn fixtures_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+    }
+
+    /// Hand-written fixture files in the execution-apis `.io` format: `>>` request and `<<` response lines
+    fn fixture_files() -> Vec<PathBuf> {
+        let mut files = vec![];
+        for method in std::fs::read_dir(fixtures_dir()).expect("fixtures dir") {
+            let method = method.expect("fixtures dir entry").path();
+            if !method.is_dir() {
+                continue;
+            }
+
+            for file in std::fs::read_dir(&method).expect("method fixtures dir") {
+                let file = file.expect("method fixtures dir entry").path();
+                if file.extension().is_some_and(|ext| ext == "io") {
+                    files.push(file);
+                }
+            }
+        }
+
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn lookup_fixtures() {
+        let chain: MockChain = serde_json::from_str(
+            &std::fs::read_to_string(fixtures_dir().join("chain.json")).expect("chain.json"),
+        )
+        .expect("valid chain.json");
+        let module = Lookup::new(Arc::new(chain)).into_rpc();
+
+        let files = fixture_files();
+        assert!(!files.is_empty(), "no fixtures found");
+
+        let mut failures = vec![];
+        for file in files {
+            let content = std::fs::read_to_string(&file).expect("fixture");
+            let mut request = None;
+            for line in content.lines() {
+                if let Some(req) = line.strip_prefix(">> ") {
+                    request = Some(req.to_string());
+                } else if let Some(expected) = line.strip_prefix("<< ") {
+                    let req = request.take().expect("response without a request");
+                    let (response, _) = module
+                        .raw_json_request(&req, 1)
+                        .await
+                        .expect("valid request");
+
+                    let response: serde_json::Value =
+                        serde_json::from_str(&response).expect("valid response");
+                    let expected: serde_json::Value =
+                        serde_json::from_str(expected).expect("valid expected response");
+                    if response != expected {
+                        failures.push(format!(
+                            "{}\n  request:  {}\n  expected: {}\n  got:      {}",
+                            file.display(),
+                            req,
+                            expected,
+                            response
+                        ));
+                    }
+                }
+            }
+        }
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
@@ -1,18 +1,28 @@
+pub mod admin;
+pub mod debug;
 pub mod eth;
+pub mod lookup;
 
+use crate::solana_providers::ProviderHealth;
+use crate::subscriptions::SubscriptionKind;
 use serde::{Deserializer, Serialize, Serializer};
 use solana_sdk::instruction::AccountMeta;
 use std::str::FromStr;
 use {
+    alloy_rpc_types_trace::geth::{
+        GethDebugTracingCallOptions, GethDebugTracingOptions, GethTrace,
+    },
     ethers::types::{
-        Address, BlockId, Bytes, FeeHistory, Transaction, TransactionReceipt, TransactionRequest,
-        TxHash, H256, U256, U64,
+        Address, BlockId, Bytes, FeeHistory, Filter, Log, Transaction, TransactionReceipt,
+        TransactionRequest, TxHash, H256, U256, U64,
     },
+    jsonrpsee::core::SubscriptionResult,
     jsonrpsee::proc_macros::rpc,
-    jsonrpsee::types::{error::CALL_EXECUTION_FAILED_CODE, ErrorObjectOwned},
+    jsonrpsee::types::{error::INVALID_PARAMS_CODE, ErrorObjectOwned},
     rome_sdk::rome_evm_client::{
         error::RomeEvmError, indexer::BlockType, rome_evm::error::RomeProgramError,
     },
+    rpc_error::{error_object, INVALID_INPUT_CODE, LIMIT_EXCEEDED_CODE},
     serde::{self, Deserialize},
     solana_client::client_error::ClientError,
     solana_sdk::pubkey::Pubkey,
@@ -32,19 +42,69 @@ pub enum ApiError {
 
     #[error("Solana client error: {0}")]
     SolanaClientError(ClientError),
+
+    #[error("Filter not found")]
+    FilterNotFound,
+
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Block not found")]
+    BlockNotFound,
+
+    #[error("Historical state not available for block {0}")]
+    HistoricalStateUnavailable(u64),
+
+    #[error("Transaction not found")]
+    TransactionNotFound,
+
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+
+    #[error("Invalid transaction: {0}")]
+    InvalidTransaction(String),
 }
 
 impl From<ApiError> for ErrorObjectOwned {
     fn from(e: ApiError) -> ErrorObjectOwned {
         match e {
             ApiError::ResponseFailed(e) => e,
-            ApiError::RomeEvmError(RomeEvmError::EmulationRevert(mes, data)) => {
-                ErrorObjectOwned::owned(3, mes, Some(data))
+            ApiError::RomeEvmError(err) => rpc_error::rome_evm_error(err),
+            ApiError::RomeProgramError(err) => rpc_error::rome_program_error(err),
+            ApiError::SolanaClientError(err) => rpc_error::solana_error(err),
+            ApiError::FilterNotFound => error_object(
+                INVALID_INPUT_CODE,
+                "filter not found".to_string(),
+                "proxy",
+                "filter_not_found",
+            ),
+            ApiError::LimitExceeded(mes) => {
+                error_object(LIMIT_EXCEEDED_CODE, mes, "proxy", "limit_exceeded")
+            }
+            ApiError::BlockNotFound => error_object(
+                INVALID_INPUT_CODE,
+                "header not found".to_string(),
+                "proxy",
+                "block_not_found",
+            ),
+            ApiError::HistoricalStateUnavailable(number) => error_object(
+                INVALID_INPUT_CODE,
+                format!("historical state not available for block {number}"),
+                "proxy",
+                "historical_state_unavailable",
+            ),
+            ApiError::TransactionNotFound => error_object(
+                INVALID_INPUT_CODE,
+                "transaction not found".to_string(),
+                "proxy",
+                "transaction_not_found",
+            ),
+            ApiError::Unsupported(mes) => {
+                error_object(INVALID_PARAMS_CODE, mes, "proxy", "unsupported")
             }
-            ApiError::RomeEvmError(RomeEvmError::EmulationError(err)) => {
-                ErrorObjectOwned::owned(3, err, None::<String>)
+            ApiError::InvalidTransaction(mes) => {
+                error_object(INVALID_INPUT_CODE, mes, "proxy", "invalid_transaction")
             }
-            _ => ErrorObjectOwned::borrowed(CALL_EXECUTION_FAILED_CODE, "", None),
         }
     }
 }
@@ -94,6 +154,30 @@ impl Serialize for B58Pubkey {
     }
 }
 
+/// Block number, tag, EIP-1898 object or a plain block hash
+#[derive(Debug, Clone, Copy)]
+pub struct BlockNumberOrHash(pub BlockId);
+
+impl<'de> Deserialize<'de> for BlockNumberOrHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        if let Some(hash) = value
+            .as_str()
+            .filter(|s| s.len() == 66)
+            .and_then(|s| H256::from_str(s).ok())
+        {
+            return Ok(Self(BlockId::Hash(hash)));
+        }
+
+        BlockId::deserialize(value)
+            .map(Self)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmulationAccountMeta {
     pub pubkey: B58Pubkey,
@@ -111,10 +195,18 @@ impl From<AccountMeta> for EmulationAccountMeta {
     }
 }
 
+/// Result of the eth_getFilterChanges
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum FilterChanges {
+    Logs(Vec<Log>),
+    Hashes(Vec<H256>),
+}
+
 #[rpc(server)]
 pub trait Eth {
     #[method(name = "eth_getBalance")]
-    async fn eth_get_balance(&self, address: Address, block: String) -> ApiResult<U256>;
+    async fn eth_get_balance(&self, address: Address, block: Option<BlockId>) -> ApiResult<U256>;
     #[method(name = "eth_chainId")]
     async fn eth_chain_id(&self) -> ApiResult<U64>;
     #[method(name = "eth_blockNumber")]
@@ -134,13 +226,17 @@ pub trait Eth {
         flag: bool,
     ) -> ApiResult<Option<BlockType>>;
     #[method(name = "eth_call")]
-    async fn eth_call(&self, call: TransactionRequest, block: String) -> ApiResult<Bytes>;
+    async fn eth_call(&self, call: TransactionRequest, block: Option<BlockId>) -> ApiResult<Bytes>;
     #[method(name = "eth_getTransactionCount")]
-    async fn eth_get_transaction_count(&self, address: Address, block: String) -> ApiResult<U64>;
+    async fn eth_get_transaction_count(
+        &self,
+        address: Address,
+        block: Option<BlockId>,
+    ) -> ApiResult<U64>;
     #[method(name = "eth_estimateGas")]
     async fn eth_estimate_gas(&self, call: TransactionRequest) -> ApiResult<U256>;
     #[method(name = "eth_getCode")]
-    async fn eth_get_code(&self, address: Address, block: String) -> ApiResult<Bytes>;
+    async fn eth_get_code(&self, address: Address, block: Option<BlockId>) -> ApiResult<Bytes>;
     #[method(name = "eth_sendRawTransaction")]
     async fn eth_send_raw_transaction(&self, rlp: Bytes) -> ApiResult<TxHash>;
     #[method(name = "net_version")]
@@ -167,7 +263,7 @@ pub trait Eth {
         &self,
         address: Address,
         slot: U256,
-        block: String,
+        block: Option<BlockId>,
     ) -> ApiResult<String>;
 
     #[method(name = "eth_maxPriorityFeePerGas")]
@@ -181,4 +277,92 @@ pub trait Eth {
 
     #[method(name = "rome_emulateTx")]
     async fn emulate_tx(&self, rlp: Bytes) -> ApiResult<()>;
+
+    #[method(name = "eth_getLogs")]
+    async fn eth_get_logs(&self, filter: Filter) -> ApiResult<Vec<Log>>;
+    #[method(name = "eth_newFilter")]
+    async fn eth_new_filter(&self, filter: Filter) -> ApiResult<U256>;
+    #[method(name = "eth_newBlockFilter")]
+    async fn eth_new_block_filter(&self) -> ApiResult<U256>;
+    #[method(name = "eth_getFilterChanges")]
+    async fn eth_get_filter_changes(&self, id: U256) -> ApiResult<FilterChanges>;
+    #[method(name = "eth_getFilterLogs")]
+    async fn eth_get_filter_logs(&self, id: U256) -> ApiResult<Vec<Log>>;
+    #[method(name = "eth_uninstallFilter")]
+    async fn eth_uninstall_filter(&self, id: U256) -> ApiResult<bool>;
+
+    #[subscription(name = "eth_subscribe" => "eth_subscription", unsubscribe = "eth_unsubscribe", item = serde_json::Value)]
+    async fn eth_subscribe(
+        &self,
+        kind: SubscriptionKind,
+        filter: Option<Filter>,
+    ) -> SubscriptionResult;
+}
+
+#[rpc(server)]
+pub trait EthLookup {
+    #[method(name = "eth_getBlockTransactionCountByNumber")]
+    async fn eth_get_block_transaction_count_by_number(
+        &self,
+        block_number: BlockId,
+    ) -> ApiResult<Option<U64>>;
+    #[method(name = "eth_getBlockTransactionCountByHash")]
+    async fn eth_get_block_transaction_count_by_hash(
+        &self,
+        block_hash: H256,
+    ) -> ApiResult<Option<U64>>;
+    #[method(name = "eth_getTransactionByBlockNumberAndIndex")]
+    async fn eth_get_transaction_by_block_number_and_index(
+        &self,
+        block_number: BlockId,
+        index: U64,
+    ) -> ApiResult<Option<Transaction>>;
+    #[method(name = "eth_getTransactionByBlockHashAndIndex")]
+    async fn eth_get_transaction_by_block_hash_and_index(
+        &self,
+        block_hash: H256,
+        index: U64,
+    ) -> ApiResult<Option<Transaction>>;
+    #[method(name = "eth_getBlockReceipts")]
+    async fn eth_get_block_receipts(
+        &self,
+        block: BlockNumberOrHash,
+    ) -> ApiResult<Option<Vec<TransactionReceipt>>>;
+    #[method(name = "eth_getUncleCountByBlockNumber")]
+    async fn eth_get_uncle_count_by_block_number(
+        &self,
+        block_number: BlockId,
+    ) -> ApiResult<Option<U64>>;
+    #[method(name = "eth_syncing")]
+    async fn eth_syncing(&self) -> ApiResult<bool>;
+    #[method(name = "eth_accounts")]
+    async fn eth_accounts(&self) -> ApiResult<Vec<Address>>;
+    #[method(name = "net_listening")]
+    async fn net_listening(&self) -> ApiResult<bool>;
+    #[method(name = "net_peerCount")]
+    async fn net_peer_count(&self) -> ApiResult<U64>;
+}
+
+#[rpc(server)]
+pub trait Debug {
+    #[method(name = "debug_traceTransaction")]
+    async fn debug_trace_transaction(
+        &self,
+        tx_hash: H256,
+        options: Option<GethDebugTracingOptions>,
+    ) -> ApiResult<GethTrace>;
+
+    #[method(name = "debug_traceCall")]
+    async fn debug_trace_call(
+        &self,
+        call: TransactionRequest,
+        block: Option<BlockId>,
+        options: Option<GethDebugTracingCallOptions>,
+    ) -> ApiResult<GethTrace>;
+}
+
+#[rpc(server)]
+pub trait Admin {
+    #[method(name = "solanaProviders")]
+    async fn solana_providers(&self) -> ApiResult<Vec<ProviderHealth>>;
 }
@@ -0,0 +1,605 @@
+use ethers::types::{Block, TxHash};
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::server::{MethodResponse, ResponsePayload};
+use jsonrpsee::types::error::INTERNAL_ERROR_CODE;
+use jsonrpsee::types::{ErrorObjectOwned, Request};
+use lru::LruCache;
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::OnceCell;
+
+const DEFAULT_MAX_ENTRIES: usize = 10_000;
+const DEFAULT_FINALITY_DEPTH: u64 = 32;
+const DEFAULT_MAX_MUTABLE_AGE_MS: u64 = 2_000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct CacheConfig {
+    /// Maximum number of cached responses of each kind (immutable and mutable)
+    pub max_entries: Option<usize>,
+    /// Number of blocks after which a block and its transactions are considered final
+    pub finality_depth: Option<u64>,
+    /// Mutable state is dropped after this period even if no new block was observed
+    pub max_mutable_age_ms: Option<u64>,
+}
+
+/// How long the response of the method stays valid
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    /// Never changes once it is available
+    Immutable,
+    /// Never changes once the block it belongs to is final
+    Final,
+    /// Valid until the next block
+    Latest,
+}
+
+type Outcome = Result<Box<RawValue>, ErrorObjectOwned>;
+
+struct MutableEntry {
+    epoch: u64,
+    created: Instant,
+    result: Box<RawValue>,
+}
+
+/// In-process cache of the RPC responses.
+/// Immutable responses are kept until evicted by the LRU bound,
+/// responses depending on the latest state are dropped on every new block.
+pub struct ResponseCache {
+    immutable: Mutex<LruCache<String, Box<RawValue>>>,
+    mutable: Mutex<LruCache<String, MutableEntry>>,
+    in_flight: Mutex<HashMap<String, Arc<OnceCell<Outcome>>>>,
+    epoch: AtomicU64,
+    latest_block: AtomicU64,
+    finality_depth: u64,
+    max_mutable_age: Duration,
+}
+
+impl ResponseCache {
+    pub fn new(config: &CacheConfig) -> Self {
+        let max_entries = NonZeroUsize::new(config.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES))
+            .unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            immutable: Mutex::new(LruCache::new(max_entries)),
+            mutable: Mutex::new(LruCache::new(max_entries)),
+            in_flight: Mutex::new(HashMap::new()),
+            epoch: AtomicU64::new(0),
+            latest_block: AtomicU64::new(0),
+            finality_depth: config.finality_depth.unwrap_or(DEFAULT_FINALITY_DEPTH),
+            max_mutable_age: Duration::from_millis(
+                config
+                    .max_mutable_age_ms
+                    .unwrap_or(DEFAULT_MAX_MUTABLE_AGE_MS),
+            ),
+        }
+    }
+
+    /// Invalidate mutable state on every new block
+    pub async fn start(self: Arc<Self>, mut heads: broadcast::Receiver<Arc<Block<TxHash>>>) {
+        loop {
+            match heads.recv().await {
+                Ok(block) => {
+                    if let Some(number) = block.number {
+                        self.latest_block
+                            .fetch_max(number.as_u64(), Ordering::Relaxed);
+                    }
+                    self.invalidate();
+                }
+                Err(RecvError::Lagged(_)) => self.invalidate(),
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    /// Drop all responses depending on the latest state
+    pub fn invalidate(&self) {
+        self.epoch.fetch_add(1, Ordering::Relaxed);
+        self.mutable.lock().expect("cache lock poisoned").clear();
+    }
+
+    fn get(&self, key: &str) -> Option<Box<RawValue>> {
+        if let Some(result) = self.immutable.lock().expect("cache lock poisoned").get(key) {
+            return Some(result.clone());
+        }
+
+        let mut mutable = self.mutable.lock().expect("cache lock poisoned");
+        match mutable.get(key) {
+            Some(entry)
+                if entry.epoch == self.epoch.load(Ordering::Relaxed)
+                    && entry.created.elapsed() < self.max_mutable_age =>
+            {
+                Some(entry.result.clone())
+            }
+            Some(_) => {
+                mutable.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, key: String, scope: Scope, epoch: u64, result: &RawValue) {
+        match self.resolve_scope(scope, result) {
+            Scope::Immutable => {
+                self.immutable
+                    .lock()
+                    .expect("cache lock poisoned")
+                    .put(key, result.to_owned());
+            }
+            _ => {
+                // The response may be outdated if a new block was observed in the meantime
+                if epoch != self.epoch.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                self.mutable.lock().expect("cache lock poisoned").put(
+                    key,
+                    MutableEntry {
+                        epoch,
+                        created: Instant::now(),
+                        result: result.to_owned(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Decide how long the particular response stays valid
+    fn resolve_scope(&self, scope: Scope, result: &RawValue) -> Scope {
+        let value = match serde_json::from_str::<Value>(result.get()) {
+            Ok(value) => value,
+            Err(_) => return Scope::Latest,
+        };
+
+        match scope {
+            // Missing blocks can appear later
+            Scope::Immutable if value.is_null() => Scope::Latest,
+            Scope::Final => {
+                let latest = self.latest_block.load(Ordering::Relaxed);
+                match value
+                    .get("blockNumber")
+                    .or_else(|| value.get("number"))
+                    .and_then(parse_quantity)
+                {
+                    Some(number) if latest > 0 && number + self.finality_depth <= latest => {
+                        Scope::Immutable
+                    }
+                    _ => Scope::Latest,
+                }
+            }
+            scope => scope,
+        }
+    }
+
+    /// Run the request once for all identical concurrent requests
+    async fn coalesce<F>(&self, key: &str, response: F) -> (Outcome, Option<MethodResponse>)
+    where
+        F: Future<Output = MethodResponse>,
+    {
+        let cell = self
+            .in_flight
+            .lock()
+            .expect("cache lock poisoned")
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+
+        let mut own = None;
+        let call = async {
+            let response = response.await;
+            let outcome = outcome(&response);
+            own = Some(response);
+            outcome
+        };
+        let outcome = cell.get_or_init(|| call).await.clone();
+
+        let mut in_flight = self.in_flight.lock().expect("cache lock poisoned");
+        if in_flight
+            .get(key)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(key);
+        }
+
+        (outcome, own)
+    }
+}
+
+/// RPC middleware serving responses from the [ResponseCache]
+#[derive(Clone)]
+pub struct CachedService<S> {
+    service: S,
+    cache: Option<Arc<ResponseCache>>,
+    max_response_size: usize,
+}
+
+impl<S> CachedService<S> {
+    pub fn new(service: S, cache: Option<Arc<ResponseCache>>, max_response_size: u32) -> Self {
+        Self {
+            service,
+            cache,
+            max_response_size: max_response_size as usize,
+        }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for CachedService<S>
+where
+    S: RpcServiceT<'a>,
+    S::Future: 'a,
+{
+    type Future = Pin<Box<dyn Future<Output = MethodResponse> + Send + 'a>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let Some(cache) = self.cache.clone() else {
+            return Box::pin(self.service.call(request));
+        };
+
+        let method = request.method_name().to_string();
+        if method == "eth_sendRawTransaction" {
+            let response = self.service.call(request);
+            return Box::pin(async move {
+                let response = response.await;
+                if response.is_success() {
+                    cache.invalidate();
+                }
+                response
+            });
+        }
+
+        let params = request.params.as_ref().map(|params| params.get());
+        let Some(scope) = scope(&method, params) else {
+            return Box::pin(self.service.call(request));
+        };
+
+        let id = request.id().into_owned();
+        let max_response_size = self.max_response_size;
+        let key = format!("{}:{}", method, params.unwrap_or_default());
+        if let Some(result) = cache.get(&key) {
+            return Box::pin(async move {
+                MethodResponse::response(id, ResponsePayload::success(result), max_response_size)
+            });
+        }
+
+        let epoch = cache.epoch.load(Ordering::Relaxed);
+        let response = self.service.call(request);
+        Box::pin(async move {
+            let (outcome, own) = cache.coalesce(&key, response).await;
+            if let Ok(result) = &outcome {
+                cache.insert(key, scope, epoch, result);
+            }
+
+            match (own, outcome) {
+                (Some(response), _) => response,
+                (None, Ok(result)) => MethodResponse::response(
+                    id,
+                    ResponsePayload::success(result),
+                    max_response_size,
+                ),
+                (None, Err(err)) => MethodResponse::error(id, err),
+            }
+        })
+    }
+}
+
+/// Parse the result or the error of the response
+fn outcome(response: &MethodResponse) -> Outcome {
+    #[derive(serde::Deserialize)]
+    struct Response {
+        result: Option<Box<RawValue>>,
+        error: Option<ErrorObjectOwned>,
+    }
+
+    match serde_json::from_str::<Response>(response.as_result()) {
+        Ok(Response {
+            result: Some(result),
+            ..
+        }) => Ok(result),
+        Ok(Response {
+            error: Some(err), ..
+        }) => Err(err),
+        _ => Err(ErrorObjectOwned::owned(
+            INTERNAL_ERROR_CODE,
+            "invalid response",
+            None::<()>,
+        )),
+    }
+}
+
+/// Scope of the method response or None if the method is not cached
+fn scope(method: &str, params: Option<&str>) -> Option<Scope> {
+    let params = params
+        .and_then(|params| serde_json::from_str::<Vec<Value>>(params).ok())
+        .unwrap_or_default();
+    let at_latest = |idx: usize| match params.get(idx) {
+        None | Some(Value::Null) => true,
+        Some(block) => block == "latest",
+    };
+
+    match method {
+        "eth_chainId" | "eth_getBlockByHash" => Some(Scope::Immutable),
+        "eth_getTransactionReceipt" | "eth_getTransactionByHash" => Some(Scope::Final),
+        "eth_getBlockByNumber" => match params.first() {
+            Some(Value::String(tag)) if tag.starts_with("0x") => Some(Scope::Final),
+            _ if at_latest(0) => Some(Scope::Latest),
+            _ => None,
+        },
+        // Code of the address changes on contract deployment and self-destruct
+        "eth_getBalance" | "eth_getTransactionCount" | "eth_getCode" | "eth_call"
+            if at_latest(1) =>
+        {
+            Some(Scope::Latest)
+        }
+        "eth_getStorageAt" if at_latest(2) => Some(Scope::Latest),
+        _ => None,
+    }
+}
+
+fn parse_quantity(value: &Value) -> Option<u64> {
+    u64::from_str_radix(value.as_str()?.strip_prefix("0x")?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::U64;
+    use jsonrpsee::types::Id;
+    use serde_json::json;
+    use std::borrow::Cow;
+    use std::future::Ready;
+
+    /// RPC service answering every request with the same result
+    #[derive(Clone)]
+    struct StubService {
+        result: Arc<Mutex<Value>>,
+        calls: Arc<AtomicU64>,
+    }
+
+    impl StubService {
+        fn new(result: Value) -> Self {
+            Self {
+                result: Arc::new(Mutex::new(result)),
+                calls: Arc::new(AtomicU64::new(0)),
+            }
+        }
+
+        fn set(&self, result: Value) {
+            *self.result.lock().unwrap() = result;
+        }
+
+        fn calls(&self) -> u64 {
+            self.calls.load(Ordering::Relaxed)
+        }
+    }
+
+    impl<'a> RpcServiceT<'a> for StubService {
+        type Future = Ready<MethodResponse>;
+
+        fn call(&self, request: Request<'a>) -> Self::Future {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let result = self.result.lock().unwrap().clone();
+            std::future::ready(MethodResponse::response(
+                request.id().into_owned(),
+                ResponsePayload::success(result),
+                usize::MAX,
+            ))
+        }
+    }
+
+    async fn call(service: &CachedService<StubService>, method: &str, params: Value) -> String {
+        let params = RawValue::from_string(params.to_string()).unwrap();
+        let request = Request::new(Cow::Borrowed(method), Some(&params), Id::Number(1));
+        let response = service.call(request).await;
+        response.as_result().to_string()
+    }
+
+    fn cached_service(
+        result: Value,
+    ) -> (CachedService<StubService>, StubService, Arc<ResponseCache>) {
+        let stub = StubService::new(result);
+        let cache = Arc::new(ResponseCache::new(&CacheConfig {
+            finality_depth: Some(2),
+            ..Default::default()
+        }));
+
+        (
+            CachedService::new(stub.clone(), Some(cache.clone()), 1024),
+            stub,
+            cache,
+        )
+    }
+
+    fn new_block(cache: &ResponseCache, number: u64) {
+        cache.latest_block.fetch_max(number, Ordering::Relaxed);
+        cache.invalidate();
+    }
+
+    #[test]
+    fn methods_are_scoped() {
+        let latest = json!(["0x1111111111111111111111111111111111111111", "latest"]).to_string();
+        let pending = json!(["0x1111111111111111111111111111111111111111", "pending"]).to_string();
+        let no_block = json!(["0x1111111111111111111111111111111111111111"]).to_string();
+
+        assert_eq!(scope("eth_chainId", None), Some(Scope::Immutable));
+        assert_eq!(
+            scope("eth_getBlockByHash", Some("[\"0x01\", false]")),
+            Some(Scope::Immutable)
+        );
+        assert_eq!(
+            scope("eth_getTransactionReceipt", Some("[\"0x01\"]")),
+            Some(Scope::Final)
+        );
+        assert_eq!(
+            scope("eth_getBlockByNumber", Some("[\"0x10\", false]")),
+            Some(Scope::Final)
+        );
+        assert_eq!(
+            scope("eth_getBlockByNumber", Some("[\"latest\", false]")),
+            Some(Scope::Latest)
+        );
+        assert_eq!(
+            scope("eth_getBlockByNumber", Some("[\"pending\", false]")),
+            None
+        );
+        assert_eq!(scope("eth_getCode", Some(&latest)), Some(Scope::Latest));
+        assert_eq!(
+            scope("eth_getBalance", Some(&no_block)),
+            Some(Scope::Latest)
+        );
+        assert_eq!(scope("eth_getBalance", Some(&pending)), None);
+        assert_eq!(
+            scope("eth_getStorageAt", Some("[\"0x11\", \"0x0\", \"latest\"]")),
+            Some(Scope::Latest)
+        );
+        assert_eq!(
+            scope("eth_getStorageAt", Some("[\"0x11\", \"0x0\", \"0x5\"]")),
+            None
+        );
+        assert_eq!(scope("eth_sendRawTransaction", Some("[\"0x00\"]")), None);
+        assert_eq!(scope("eth_blockNumber", None), None);
+    }
+
+    #[test]
+    fn final_responses_become_immutable() {
+        let cache = ResponseCache::new(&CacheConfig {
+            finality_depth: Some(2),
+            ..Default::default()
+        });
+        let receipt = RawValue::from_string(json!({ "blockNumber": "0xa" }).to_string()).unwrap();
+        let missing = RawValue::from_string("null".to_string()).unwrap();
+
+        // Latest block is unknown yet
+        assert_eq!(cache.resolve_scope(Scope::Final, &receipt), Scope::Latest);
+        cache.latest_block.store(11, Ordering::Relaxed);
+        assert_eq!(cache.resolve_scope(Scope::Final, &receipt), Scope::Latest);
+        cache.latest_block.store(12, Ordering::Relaxed);
+        assert_eq!(
+            cache.resolve_scope(Scope::Final, &receipt),
+            Scope::Immutable
+        );
+
+        assert_eq!(cache.resolve_scope(Scope::Final, &missing), Scope::Latest);
+        assert_eq!(
+            cache.resolve_scope(Scope::Immutable, &missing),
+            Scope::Latest
+        );
+    }
+
+    #[tokio::test]
+    async fn new_block_invalidates_latest_state() {
+        let (service, stub, cache) = cached_service(json!("0x1"));
+        let params = json!(["0x1111111111111111111111111111111111111111", "latest"]);
+
+        assert!(call(&service, "eth_getBalance", params.clone())
+            .await
+            .contains("0x1"));
+        stub.set(json!("0x2"));
+        assert!(call(&service, "eth_getBalance", params.clone())
+            .await
+            .contains("0x1"));
+        assert_eq!(stub.calls(), 1);
+
+        let (heads, receiver) = broadcast::channel(1);
+        let started = tokio::spawn(cache.clone().start(receiver));
+        let block = Block::<TxHash> {
+            number: Some(U64::from(1)),
+            ..Default::default()
+        };
+        heads.send(Arc::new(block)).unwrap();
+        drop(heads);
+        started.await.unwrap();
+
+        assert!(call(&service, "eth_getBalance", params)
+            .await
+            .contains("0x2"));
+        assert_eq!(stub.calls(), 2);
+        assert_eq!(cache.latest_block.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn code_is_not_kept_across_blocks() {
+        let (service, stub, cache) = cached_service(json!("0x"));
+        let params = json!(["0x1111111111111111111111111111111111111111", "latest"]);
+
+        call(&service, "eth_getCode", params.clone()).await;
+        stub.set(json!("0x6000"));
+        new_block(&cache, 1);
+        assert!(call(&service, "eth_getCode", params.clone())
+            .await
+            .contains("0x6000"));
+
+        stub.set(json!("0x"));
+        new_block(&cache, 2);
+        assert!(call(&service, "eth_getCode", params)
+            .await
+            .contains("\"0x\""));
+        assert_eq!(stub.calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn immutable_responses_survive_new_blocks() {
+        let (service, stub, cache) = cached_service(json!({ "number": "0x1" }));
+        let params = json!(["0x01", false]);
+
+        call(&service, "eth_getBlockByHash", params.clone()).await;
+        new_block(&cache, 5);
+        call(&service, "eth_getBlockByHash", params).await;
+        assert_eq!(stub.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn sent_transaction_invalidates_latest_state() {
+        let (service, stub, _) = cached_service(json!("0x1"));
+        let params = json!(["0x1111111111111111111111111111111111111111"]);
+
+        call(&service, "eth_getTransactionCount", params.clone()).await;
+        call(&service, "eth_sendRawTransaction", json!(["0x00"])).await;
+        call(&service, "eth_getTransactionCount", params).await;
+        assert_eq!(stub.calls(), 3);
+    }
+
+    #[test]
+    fn outdated_responses_are_not_cached() {
+        let cache = ResponseCache::new(&CacheConfig::default());
+        let result = RawValue::from_string("\"0x1\"".to_string()).unwrap();
+
+        let epoch = cache.epoch.load(Ordering::Relaxed);
+        cache.invalidate();
+        cache.insert("key".to_string(), Scope::Latest, epoch, &result);
+        assert!(cache.get("key").is_none());
+
+        cache.insert("key".to_string(), Scope::Latest, epoch + 1, &result);
+        assert!(cache.get("key").is_some());
+    }
+
+    #[test]
+    fn latest_state_expires() {
+        let cache = ResponseCache::new(&CacheConfig {
+            max_mutable_age_ms: Some(0),
+            ..Default::default()
+        });
+        let result = RawValue::from_string("\"0x1\"".to_string()).unwrap();
+
+        cache.insert("key".to_string(), Scope::Latest, 0, &result);
+        assert!(cache.get("key").is_none());
+    }
+
+    #[tokio::test]
+    async fn cached_responses_respect_max_size() {
+        let (service, stub, _) = cached_service(json!("0".repeat(2048)));
+
+        call(&service, "eth_chainId", json!([])).await;
+        let response = call(&service, "eth_chainId", json!([])).await;
+        assert!(response.contains("error"), "{response}");
+        assert_eq!(stub.calls(), 1);
+    }
+}
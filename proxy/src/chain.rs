@@ -0,0 +1,124 @@
+use crate::api::{ApiError, ApiResult};
+use crate::filters::get_block;
+use async_trait::async_trait;
+use ethers::types::{Block, BlockId, Transaction, TransactionReceipt, TxHash, H256};
+use rome_sdk::rome_evm_client::RomeEVMClient;
+
+/// Blocks, transactions and receipts of the Ethereum block storage
+#[async_trait]
+pub trait Chain: Send + Sync + 'static {
+    async fn latest_block_number(&self) -> ApiResult<u64>;
+    async fn block(&self, block_id: BlockId) -> ApiResult<Option<Block<TxHash>>>;
+    async fn transaction(&self, hash: &H256) -> ApiResult<Option<Transaction>>;
+    async fn receipt(&self, hash: &H256) -> ApiResult<Option<TransactionReceipt>>;
+}
+
+#[async_trait]
+impl Chain for RomeEVMClient {
+    async fn latest_block_number(&self) -> ApiResult<u64> {
+        Ok(self
+            .block_number()
+            .await
+            .map_err(ApiError::RomeEvmError)?
+            .as_u64())
+    }
+
+    async fn block(&self, block_id: BlockId) -> ApiResult<Option<Block<TxHash>>> {
+        get_block(self, block_id).await
+    }
+
+    async fn transaction(&self, hash: &H256) -> ApiResult<Option<Transaction>> {
+        self.get_transaction(hash)
+            .await
+            .map_err(ApiError::RomeEvmError)
+    }
+
+    async fn receipt(&self, hash: &H256) -> ApiResult<Option<TransactionReceipt>> {
+        self.get_transaction_receipt(hash)
+            .await
+            .map_err(ApiError::RomeEvmError)
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use ethers::types::{BlockNumber, Log, U64};
+
+    /// Chain served from memory
+    #[derive(serde::Deserialize, Default)]
+    pub struct MockChain {
+        pub blocks: Vec<Block<TxHash>>,
+        pub transactions: Vec<Transaction>,
+        pub receipts: Vec<TransactionReceipt>,
+    }
+
+    impl MockChain {
+        /// Append a block with a transaction for every list of logs
+        pub fn push_block(&mut self, txs: Vec<Vec<Log>>) {
+            let number = self.blocks.len() as u64;
+            let block_hash = H256::from_low_u64_be(0xb000 + number);
+            let mut block = Block::<TxHash> {
+                hash: Some(block_hash),
+                number: Some(U64::from(number)),
+                ..Default::default()
+            };
+
+            for (index, logs) in txs.into_iter().enumerate() {
+                let tx_hash = H256::from_low_u64_be((number << 16) + index as u64);
+                block.transactions.push(tx_hash);
+                self.receipts.push(TransactionReceipt {
+                    transaction_hash: tx_hash,
+                    block_hash: Some(block_hash),
+                    block_number: Some(U64::from(number)),
+                    logs,
+                    ..Default::default()
+                });
+            }
+
+            self.blocks.push(block);
+        }
+    }
+
+    #[async_trait]
+    impl Chain for MockChain {
+        async fn latest_block_number(&self) -> ApiResult<u64> {
+            Ok(self
+                .blocks
+                .last()
+                .and_then(|block| block.number)
+                .map_or(0, |number| number.as_u64()))
+        }
+
+        async fn block(&self, block_id: BlockId) -> ApiResult<Option<Block<TxHash>>> {
+            let block = match block_id {
+                BlockId::Hash(hash) => self.blocks.iter().find(|block| block.hash == Some(hash)),
+                BlockId::Number(BlockNumber::Number(number)) => self
+                    .blocks
+                    .iter()
+                    .find(|block| block.number == Some(number)),
+                BlockId::Number(BlockNumber::Earliest) => self.blocks.first(),
+                BlockId::Number(BlockNumber::Pending) => None,
+                BlockId::Number(_) => self.blocks.last(),
+            };
+
+            Ok(block.cloned())
+        }
+
+        async fn transaction(&self, hash: &H256) -> ApiResult<Option<Transaction>> {
+            Ok(self
+                .transactions
+                .iter()
+                .find(|tx| tx.hash == *hash)
+                .cloned())
+        }
+
+        async fn receipt(&self, hash: &H256) -> ApiResult<Option<TransactionReceipt>> {
+            Ok(self
+                .receipts
+                .iter()
+                .find(|receipt| receipt.transaction_hash == *hash)
+                .cloned())
+        }
+    }
+}
@@ -1,4 +1,12 @@
+use crate::api::admin::{start_rpc_server, ProxyAdmin};
+use crate::cache::{CacheConfig, ResponseCache};
+use crate::filters::{FilterConfig, Filters};
+use crate::health::{start_http_server, Health, HealthConfig};
 use crate::proxy::Proxy;
+use crate::rate_limit::{RateLimitConfig, RateLimiter};
+use crate::solana_providers::{SolanaFailoverConfig, SolanaProviders};
+use crate::state::StateClients;
+use crate::subscriptions::{SubscriptionConfig, Subscriptions};
 use jsonrpsee::server::ServerHandle;
 use rome_sdk::rome_evm_client::indexer::config::EthereumStorageConfig;
 use rome_sdk::rome_evm_client::resources::PayerConfig;
@@ -6,7 +14,10 @@ use rome_sdk::rome_evm_client::{Payer, RomeEVMClient};
 use rome_sdk::rome_solana::config::SolanaConfig;
 use rome_sdk::rome_solana::indexers::clock::SolanaClockIndexer;
 use rome_sdk::rome_solana::tower::SolanaTower;
+use solana_sdk::commitment_config::CommitmentLevel;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -21,31 +32,113 @@ pub struct ProxyConfig {
     pub proxy_host: SocketAddr,
     pub ethereum_storage: EthereumStorageConfig,
     pub gas_price: u128,
+    pub filters: Option<FilterConfig>,
+    pub subscriptions: Option<SubscriptionConfig>,
+    pub solana_failover: Option<SolanaFailoverConfig>,
+    pub admin_host: Option<SocketAddr>,
+    pub cache: Option<CacheConfig>,
+    pub rate_limit: Option<RateLimitConfig>,
+    pub health: Option<HealthConfig>,
 }
 
 impl ProxyConfig {
     pub async fn init(self) -> anyhow::Result<(ServerHandle, JoinHandle<anyhow::Result<()>>)> {
-        let rpc_client = Arc::new(self.solana.clone().into_async_client());
+        let providers = Arc::new(SolanaProviders::new(
+            &self.solana.rpc_url,
+            &self.solana_failover.clone().unwrap_or_default(),
+        ));
+        let rpc_client = Arc::new(providers.rpc_client(self.solana.commitment));
         let payers = Payer::from_config_list(&self.payers).await?;
+        let payer_pubkeys = payers.iter().map(|payer| payer.keypair.pubkey()).collect();
         let solana_clock_indexer = SolanaClockIndexer::new(rpc_client.clone()).await?;
-        let solana = SolanaTower::new(rpc_client, solana_clock_indexer.get_current_clock());
+        let solana = SolanaTower::new(rpc_client.clone(), solana_clock_indexer.get_current_clock());
         let ethereum_block_storage = self.ethereum_storage.init()?;
+        let program_id = Pubkey::from_str(&self.program_id)?;
+
+        // create rome evm clients reading state with other commitment levels, sharing the payers
+        let mut state_clients = HashMap::new();
+        for commitment in [
+            CommitmentLevel::Processed,
+            CommitmentLevel::Confirmed,
+            CommitmentLevel::Finalized,
+        ] {
+            if commitment == self.solana.commitment {
+                continue;
+            }
+
+            let client = RomeEVMClient::new(
+                self.chain_id,
+                program_id,
+                SolanaTower::new(
+                    Arc::new(providers.rpc_client(commitment)),
+                    solana_clock_indexer.get_current_clock(),
+                ),
+                commitment,
+                ethereum_block_storage.clone(),
+                payers.clone(),
+                self.gas_price.into(),
+            );
+            state_clients.insert(commitment, Arc::new(client));
+        }
 
         // create rome evm client
         let rome_evm_client = Arc::new(RomeEVMClient::new(
             self.chain_id,
-            Pubkey::from_str(&self.program_id)?,
+            program_id,
             solana,
             self.solana.commitment,
             ethereum_block_storage,
             payers,
             self.gas_price.into(),
         ));
+        let state = StateClients::new(
+            rome_evm_client.clone(),
+            self.solana.commitment,
+            state_clients,
+        );
 
         // Start the proxy server
-        let server = Proxy::new(rome_evm_client.clone())
-            .start_rpc_server(self.proxy_host)
-            .await?;
+        let filters = Filters::new(&self.filters.unwrap_or_default());
+        let subscriptions = Arc::new(Subscriptions::new(&self.subscriptions.unwrap_or_default()));
+        let cache = self
+            .cache
+            .map(|config| Arc::new(ResponseCache::new(&config)));
+        if let Some(cache) = &cache {
+            tokio::spawn(cache.clone().start(subscriptions.subscribe_heads()));
+        }
+
+        let server = Proxy::new(
+            rome_evm_client.clone(),
+            state,
+            filters,
+            subscriptions.clone(),
+            cache,
+            self.rate_limit
+                .map(|config| Arc::new(RateLimiter::new(&config))),
+        )
+        .start_rpc_server(self.proxy_host)
+        .await?;
+
+        tokio::spawn(subscriptions.start(rome_evm_client.clone()));
+        tokio::spawn(providers.clone().start_health_checks());
+
+        // Start the admin server
+        if let Some(admin_host) = self.admin_host {
+            let admin = start_rpc_server(Arc::new(ProxyAdmin::new(providers)), admin_host).await?;
+            tokio::spawn(admin.stopped());
+        }
+
+        // Start the health and metrics server
+        if let Some(config) = self.health {
+            let health = Arc::new(Health::new(
+                rpc_client,
+                solana_clock_indexer.get_current_clock(),
+                payer_pubkeys,
+                &config,
+            ));
+            tokio::spawn(health.clone().start());
+            start_http_server(health, config.host).await?;
+        }
 
         let join_handle = tokio::spawn(solana_clock_indexer.clone().start());
         Ok((server, join_handle))
@@ -0,0 +1,558 @@
+use crate::api::{ApiError, ApiResult, FilterChanges};
+use crate::chain::Chain;
+use ethers::types::{
+    Address, Block, BlockId, BlockNumber, Filter, FilterBlockOption, Log, Topic, TxHash,
+    ValueOrArray, H256, U256,
+};
+use futures::{future, stream, StreamExt, TryStreamExt};
+use jsonrpsee::types::{error::INTERNAL_ERROR_CODE, ErrorObjectOwned};
+use rome_sdk::rome_evm_client::RomeEVMClient;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+const DEFAULT_FILTER_TTL_SEC: u64 = 300;
+const DEFAULT_MAX_FILTERS: usize = 10_000;
+const DEFAULT_MAX_BLOCK_RANGE: u64 = 1_000;
+const DEFAULT_MAX_LOGS: usize = 10_000;
+/// Blocks of a range loaded concurrently
+const BLOCK_FETCH_CONCURRENCY: usize = 16;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct FilterConfig {
+    /// Filter is removed if it was not polled during this period
+    pub filter_ttl_sec: Option<u64>,
+    /// Maximum number of installed filters
+    pub max_filters: Option<usize>,
+    /// Maximum number of blocks a single log query can span
+    pub max_block_range: Option<u64>,
+    /// Maximum number of logs a single log query can return
+    pub max_logs: Option<usize>,
+}
+
+enum FilterKind {
+    Logs {
+        filter: Box<Filter>,
+        next_block: u64,
+    },
+    Blocks {
+        next_block: u64,
+    },
+}
+
+struct InstalledFilter {
+    kind: FilterKind,
+    last_poll: Instant,
+}
+
+/// Installed eth filters and log queries against the Ethereum block storage
+pub struct Filters {
+    filters: Mutex<HashMap<U256, InstalledFilter>>,
+    next_id: AtomicU64,
+    filter_ttl: Duration,
+    max_filters: usize,
+    max_block_range: u64,
+    max_logs: usize,
+}
+
+impl Filters {
+    pub fn new(config: &FilterConfig) -> Self {
+        Self {
+            filters: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            filter_ttl: Duration::from_secs(
+                config.filter_ttl_sec.unwrap_or(DEFAULT_FILTER_TTL_SEC),
+            ),
+            max_filters: config.max_filters.unwrap_or(DEFAULT_MAX_FILTERS),
+            max_block_range: config.max_block_range.unwrap_or(DEFAULT_MAX_BLOCK_RANGE),
+            max_logs: config.max_logs.unwrap_or(DEFAULT_MAX_LOGS),
+        }
+    }
+
+    /// Install a log filter, changes are reported starting from the next block
+    pub async fn new_log_filter(&self, client: &impl Chain, filter: Filter) -> ApiResult<U256> {
+        let next_block = match filter.block_option {
+            FilterBlockOption::Range {
+                from_block: Some(BlockNumber::Number(number)),
+                ..
+            } => number.as_u64(),
+            _ => client.latest_block_number().await? + 1,
+        };
+
+        self.install(FilterKind::Logs {
+            filter: Box::new(filter),
+            next_block,
+        })
+        .await
+    }
+
+    /// Install a filter reporting hashes of the new blocks
+    pub async fn new_block_filter(&self, client: &impl Chain) -> ApiResult<U256> {
+        let next_block = client.latest_block_number().await? + 1;
+        self.install(FilterKind::Blocks { next_block }).await
+    }
+
+    pub async fn uninstall(&self, id: U256) -> bool {
+        self.filters.lock().await.remove(&id).is_some()
+    }
+
+    /// Return changes since the last poll and advance the filter
+    pub async fn changes(&self, client: &impl Chain, id: U256) -> ApiResult<FilterChanges> {
+        let latest = client.latest_block_number().await?;
+        let (filter, from, to) = {
+            let mut filters = self.filters.lock().await;
+            self.remove_expired(&mut filters);
+            let installed = filters.get_mut(&id).ok_or(ApiError::FilterNotFound)?;
+            installed.last_poll = Instant::now();
+
+            match &mut installed.kind {
+                FilterKind::Logs { filter, next_block } => {
+                    let to_block = filter
+                        .get_to_block()
+                        .map_or(latest, |to| to.as_u64().min(latest));
+                    let from = *next_block;
+                    let to = self.clamp_range(from, to_block);
+                    *next_block = (*next_block).max(to + 1);
+                    (Some(filter.clone()), from, to)
+                }
+                FilterKind::Blocks { next_block } => {
+                    let from = *next_block;
+                    let to = self.clamp_range(from, latest);
+                    *next_block = (*next_block).max(to + 1);
+                    (None, from, to)
+                }
+            }
+        };
+
+        match filter {
+            Some(filter) => Ok(FilterChanges::Logs(
+                self.logs_in_range(client, &filter, from, to).await?,
+            )),
+            None => {
+                let blocks = stream::iter(from..=to)
+                    .map(|number| client.block(BlockId::Number(number.into())))
+                    .buffered(BLOCK_FETCH_CONCURRENCY)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                Ok(FilterChanges::Hashes(
+                    blocks
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|block| block.hash)
+                        .collect(),
+                ))
+            }
+        }
+    }
+
+    /// Return all logs matching an installed log filter
+    pub async fn filter_logs(&self, client: &impl Chain, id: U256) -> ApiResult<Vec<Log>> {
+        let filter = {
+            let mut filters = self.filters.lock().await;
+            self.remove_expired(&mut filters);
+            let installed = filters.get_mut(&id).ok_or(ApiError::FilterNotFound)?;
+            installed.last_poll = Instant::now();
+
+            match &installed.kind {
+                FilterKind::Logs { filter, .. } => filter.clone(),
+                FilterKind::Blocks { .. } => return Err(ApiError::FilterNotFound),
+            }
+        };
+
+        self.get_logs(client, &filter).await
+    }
+
+    /// Return all logs matching the filter
+    pub async fn get_logs(&self, client: &impl Chain, filter: &Filter) -> ApiResult<Vec<Log>> {
+        if let FilterBlockOption::AtBlockHash(hash) = filter.block_option {
+            let Some(block) = client.block(BlockId::Hash(hash)).await? else {
+                return Ok(vec![]);
+            };
+
+            let mut logs = vec![];
+            self.push_matching(filter, block_logs(client, &block).await?, &mut logs)?;
+            return Ok(logs);
+        }
+
+        let latest = client.latest_block_number().await?;
+        let from = resolve_block_number(filter.block_option.get_from_block(), latest);
+        let to = resolve_block_number(filter.block_option.get_to_block(), latest).min(latest);
+        if from > to {
+            return Ok(vec![]);
+        }
+
+        if to - from + 1 > self.max_block_range {
+            return Err(ApiError::LimitExceeded(format!(
+                "block range is limited to {} blocks",
+                self.max_block_range
+            )));
+        }
+
+        self.logs_in_range(client, filter, from, to).await
+    }
+
+    /// Blocks are loaded concurrently and their logs are matched in the block order
+    async fn logs_in_range(
+        &self,
+        client: &impl Chain,
+        filter: &Filter,
+        from: u64,
+        to: u64,
+    ) -> ApiResult<Vec<Log>> {
+        let mut blocks = stream::iter(from..=to)
+            .map(|number| async move {
+                match client.block(BlockId::Number(number.into())).await? {
+                    Some(block) => block_logs(client, &block).await,
+                    None => Ok(vec![]),
+                }
+            })
+            .buffered(BLOCK_FETCH_CONCURRENCY);
+
+        let mut logs = vec![];
+        while let Some(block_logs) = blocks.try_next().await? {
+            self.push_matching(filter, block_logs, &mut logs)?;
+        }
+
+        Ok(logs)
+    }
+
+    fn push_matching(
+        &self,
+        filter: &Filter,
+        block_logs: Vec<Log>,
+        logs: &mut Vec<Log>,
+    ) -> ApiResult<()> {
+        for log in block_logs {
+            if !matches_filter(filter, &log) {
+                continue;
+            }
+
+            if logs.len() >= self.max_logs {
+                return Err(ApiError::LimitExceeded(format!(
+                    "query returned more than {} results",
+                    self.max_logs
+                )));
+            }
+
+            logs.push(log);
+        }
+
+        Ok(())
+    }
+
+    async fn install(&self, kind: FilterKind) -> ApiResult<U256> {
+        let mut filters = self.filters.lock().await;
+        self.remove_expired(&mut filters);
+        if filters.len() >= self.max_filters {
+            return Err(ApiError::LimitExceeded(format!(
+                "number of filters is limited to {}",
+                self.max_filters
+            )));
+        }
+
+        let id = U256::from(self.next_id.fetch_add(1, Ordering::Relaxed));
+        filters.insert(
+            id,
+            InstalledFilter {
+                kind,
+                last_poll: Instant::now(),
+            },
+        );
+
+        Ok(id)
+    }
+
+    fn remove_expired(&self, filters: &mut HashMap<U256, InstalledFilter>) {
+        filters.retain(|_, installed| installed.last_poll.elapsed() < self.filter_ttl);
+    }
+
+    fn clamp_range(&self, from: u64, to: u64) -> u64 {
+        to.min(from.saturating_add(self.max_block_range).saturating_sub(1))
+    }
+}
+
+fn resolve_block_number(block: Option<&BlockNumber>, latest: u64) -> u64 {
+    match block {
+        Some(BlockNumber::Number(number)) => number.as_u64(),
+        Some(BlockNumber::Earliest) => 0,
+        _ => latest,
+    }
+}
+
+/// Load a block with transaction hashes from the Ethereum block storage
+pub async fn get_block(
+    client: &RomeEVMClient,
+    block_id: BlockId,
+) -> ApiResult<Option<Block<TxHash>>> {
+    let Some(block) = client.get_block(block_id, false).await? else {
+        return Ok(None);
+    };
+
+    serde_json::to_value(block)
+        .and_then(serde_json::from_value)
+        .map(Some)
+        .map_err(|err| {
+            ApiError::ResponseFailed(ErrorObjectOwned::owned(
+                INTERNAL_ERROR_CODE,
+                err.to_string(),
+                None::<()>,
+            ))
+        })
+}
+
+/// Load logs of all transactions included into the block, receipts are loaded concurrently
+pub async fn block_logs(client: &impl Chain, block: &Block<TxHash>) -> ApiResult<Vec<Log>> {
+    let receipts = future::try_join_all(
+        block
+            .transactions
+            .iter()
+            .map(|tx_hash| client.receipt(tx_hash)),
+    )
+    .await?;
+
+    Ok(receipts
+        .into_iter()
+        .flatten()
+        .flat_map(|receipt| receipt.logs)
+        .collect())
+}
+
+/// Check if the log matches address and topics of the filter
+pub fn matches_filter(filter: &Filter, log: &Log) -> bool {
+    matches_address(&filter.address, &log.address) && matches_topics(&filter.topics, &log.topics)
+}
+
+fn matches_address(filter: &Option<ValueOrArray<Address>>, address: &Address) -> bool {
+    match filter {
+        None => true,
+        Some(ValueOrArray::Value(expected)) => expected == address,
+        Some(ValueOrArray::Array(expected)) => expected.is_empty() || expected.contains(address),
+    }
+}
+
+fn matches_topics(filter: &[Option<Topic>; 4], topics: &[H256]) -> bool {
+    filter.iter().enumerate().all(|(idx, topic)| {
+        let actual = topics.get(idx);
+        match topic {
+            None | Some(ValueOrArray::Value(None)) => true,
+            Some(ValueOrArray::Value(Some(expected))) => actual == Some(expected),
+            Some(ValueOrArray::Array(expected)) => {
+                expected.is_empty()
+                    || expected.iter().any(|expected| match expected {
+                        None => true,
+                        Some(expected) => actual == Some(expected),
+                    })
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::MockChain;
+
+    fn topic(n: u64) -> H256 {
+        H256::from_low_u64_be(n)
+    }
+
+    fn log(address: u64, topics: &[u64]) -> Log {
+        Log {
+            address: Address::from_low_u64_be(address),
+            topics: topics.iter().map(|n| topic(*n)).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Blocks 0..count with a transaction emitting one log of the address 1 each
+    fn chain(count: u64) -> MockChain {
+        let mut chain = MockChain::default();
+        for number in 0..count {
+            chain.push_block(vec![vec![log(1, &[number])]]);
+        }
+
+        chain
+    }
+
+    fn range(from: u64, to: u64) -> Filter {
+        Filter::new().from_block(from).to_block(to)
+    }
+
+    #[test]
+    fn topics_match_by_position() {
+        let log = log(1, &[1, 2, 3]);
+        assert!(matches_filter(&Filter::new(), &log));
+        assert!(matches_filter(&Filter::new().topic0(topic(1)), &log));
+        assert!(matches_filter(&Filter::new().topic2(topic(3)), &log));
+        assert!(!matches_filter(&Filter::new().topic0(topic(2)), &log));
+        assert!(!matches_filter(&Filter::new().topic3(topic(4)), &log));
+    }
+
+    #[test]
+    fn null_topic_is_a_wildcard() {
+        let log = log(1, &[1, 2]);
+        let mut filter = Filter::new().topic1(topic(2));
+        filter.topics[0] = Some(ValueOrArray::Value(None));
+        assert!(matches_filter(&filter, &log));
+
+        filter.topics[0] = Some(ValueOrArray::Array(vec![None, Some(topic(9))]));
+        assert!(matches_filter(&filter, &log));
+    }
+
+    #[test]
+    fn topic_array_matches_any_of() {
+        let log = log(1, &[1, 2]);
+        assert!(matches_filter(
+            &Filter::new().topic1(vec![topic(5), topic(2)]),
+            &log
+        ));
+        assert!(!matches_filter(
+            &Filter::new().topic1(vec![topic(5), topic(6)]),
+            &log
+        ));
+        assert!(matches_filter(
+            &Filter::new().topic1(Vec::<H256>::new()),
+            &log
+        ));
+    }
+
+    #[test]
+    fn address_matches_any_of() {
+        let log = log(2, &[]);
+        assert!(matches_filter(
+            &Filter::new().address(Address::from_low_u64_be(2)),
+            &log
+        ));
+        assert!(matches_filter(
+            &Filter::new().address(vec![
+                Address::from_low_u64_be(1),
+                Address::from_low_u64_be(2)
+            ]),
+            &log
+        ));
+        assert!(!matches_filter(
+            &Filter::new().address(Address::from_low_u64_be(1)),
+            &log
+        ));
+    }
+
+    #[tokio::test]
+    async fn logs_are_returned_in_block_order() {
+        let chain = chain(40);
+        let filters = Filters::new(&FilterConfig::default());
+
+        let logs = filters.get_logs(&chain, &range(5, 34)).await.unwrap();
+        let topics = logs.iter().map(|log| log.topics[0]).collect::<Vec<_>>();
+        assert_eq!(topics, (5..=34).map(topic).collect::<Vec<_>>());
+
+        let logs = filters
+            .get_logs(&chain, &range(0, 39).topic0(vec![topic(3), topic(30)]))
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn block_range_is_limited() {
+        let chain = chain(20);
+        let filters = Filters::new(&FilterConfig {
+            max_block_range: Some(10),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            filters.get_logs(&chain, &range(0, 9)).await.unwrap().len(),
+            10
+        );
+        assert!(matches!(
+            filters.get_logs(&chain, &range(0, 10)).await,
+            Err(ApiError::LimitExceeded(_))
+        ));
+        // The range ends at the latest block
+        assert_eq!(
+            filters
+                .get_logs(&chain, &range(15, 100))
+                .await
+                .unwrap()
+                .len(),
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn number_of_logs_is_limited() {
+        let chain = chain(20);
+        let filters = Filters::new(&FilterConfig {
+            max_logs: Some(10),
+            ..Default::default()
+        });
+
+        assert!(matches!(
+            filters.get_logs(&chain, &range(0, 10)).await,
+            Err(ApiError::LimitExceeded(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn changes_advance_the_filter() {
+        let mut chain = chain(3);
+        let filters = Filters::new(&FilterConfig::default());
+        let id = filters.new_block_filter(&chain).await.unwrap();
+        let log_id = filters.new_log_filter(&chain, Filter::new()).await.unwrap();
+
+        chain.push_block(vec![vec![log(1, &[3])]]);
+        chain.push_block(vec![]);
+        let FilterChanges::Hashes(hashes) = filters.changes(&chain, id).await.unwrap() else {
+            panic!("block filter returned logs");
+        };
+        assert_eq!(
+            hashes,
+            vec![chain.blocks[3].hash.unwrap(), chain.blocks[4].hash.unwrap()]
+        );
+        let FilterChanges::Logs(logs) = filters.changes(&chain, log_id).await.unwrap() else {
+            panic!("log filter returned hashes");
+        };
+        assert_eq!(logs.len(), 1);
+
+        let FilterChanges::Hashes(hashes) = filters.changes(&chain, id).await.unwrap() else {
+            panic!("block filter returned logs");
+        };
+        assert!(hashes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_filters_are_removed() {
+        let chain = chain(3);
+        let filters = Filters::new(&FilterConfig {
+            filter_ttl_sec: Some(0),
+            ..Default::default()
+        });
+
+        let id = filters.new_block_filter(&chain).await.unwrap();
+        assert!(matches!(
+            filters.changes(&chain, id).await,
+            Err(ApiError::FilterNotFound)
+        ));
+        assert!(!filters.uninstall(id).await);
+    }
+
+    #[tokio::test]
+    async fn polled_filters_are_kept() {
+        let chain = chain(3);
+        let filters = Filters::new(&FilterConfig {
+            filter_ttl_sec: Some(60),
+            max_filters: Some(1),
+            ..Default::default()
+        });
+
+        let id = filters.new_block_filter(&chain).await.unwrap();
+        assert!(filters.changes(&chain, id).await.is_ok());
+        assert!(matches!(
+            filters.new_block_filter(&chain).await,
+            Err(ApiError::LimitExceeded(_))
+        ));
+        assert!(filters.uninstall(id).await);
+        assert!(filters.new_block_filter(&chain).await.is_ok());
+    }
+}
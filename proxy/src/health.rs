@@ -0,0 +1,204 @@
+use crate::metrics::{self, PAYER_BALANCE, SOLANA_CLOCK_LAG};
+use jsonrpsee::core::BoxError;
+use jsonrpsee::server::{serve, HttpBody, HttpRequest, HttpResponse};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::clock::Clock;
+use solana_sdk::pubkey::Pubkey;
+use std::future::Ready;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+const DEFAULT_CHECK_INTERVAL_MS: u64 = 5_000;
+const DEFAULT_MAX_CLOCK_LAG_SEC: u64 = 60;
+const DEFAULT_MIN_PAYER_BALANCE: u64 = 100_000_000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct HealthConfig {
+    /// Address of the HTTP server serving /health, /ready and /metrics
+    pub host: SocketAddr,
+    /// Interval of the readiness checks
+    pub check_interval_ms: Option<u64>,
+    /// The proxy is not ready if the clock of the Solana clock indexer is behind the local time more than this
+    pub max_clock_lag_sec: Option<u64>,
+    /// The proxy is not ready if all payers have less lamports than this
+    pub min_payer_balance: Option<u64>,
+}
+
+/// Readiness of the proxy to serve requests
+pub struct Health {
+    rpc_client: Arc<RpcClient>,
+    clock: Arc<RwLock<Clock>>,
+    payers: Vec<Pubkey>,
+    check_interval: Duration,
+    max_clock_lag: u64,
+    min_payer_balance: u64,
+    clock_ok: AtomicBool,
+    payers_ok: AtomicBool,
+}
+
+impl Health {
+    /// `clock` is the current clock of the Solana clock indexer, it goes stale if the indexer stops
+    pub fn new(
+        rpc_client: Arc<RpcClient>,
+        clock: Arc<RwLock<Clock>>,
+        payers: Vec<Pubkey>,
+        config: &HealthConfig,
+    ) -> Self {
+        Self {
+            rpc_client,
+            clock,
+            payers,
+            check_interval: Duration::from_millis(
+                config
+                    .check_interval_ms
+                    .unwrap_or(DEFAULT_CHECK_INTERVAL_MS),
+            ),
+            max_clock_lag: config
+                .max_clock_lag_sec
+                .unwrap_or(DEFAULT_MAX_CLOCK_LAG_SEC),
+            min_payer_balance: config
+                .min_payer_balance
+                .unwrap_or(DEFAULT_MIN_PAYER_BALANCE),
+            clock_ok: AtomicBool::new(false),
+            payers_ok: AtomicBool::new(false),
+        }
+    }
+
+    /// Periodically check the Solana clock indexer and the payer balances
+    pub async fn start(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.check_interval);
+        loop {
+            interval.tick().await;
+            self.clock_ok
+                .store(self.check_clock().await, Ordering::Relaxed);
+            self.payers_ok
+                .store(self.check_payers().await, Ordering::Relaxed);
+        }
+    }
+
+    async fn check_clock(&self) -> bool {
+        let timestamp = self.clock.read().await.unix_timestamp;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let lag = now.saturating_sub(timestamp);
+        SOLANA_CLOCK_LAG.set(lag);
+        if lag > self.max_clock_lag as i64 {
+            tracing::warn!(
+                "Health: Solana clock indexer is {} seconds behind, it may be stalled",
+                lag
+            );
+            return false;
+        }
+
+        true
+    }
+
+    async fn check_payers(&self) -> bool {
+        if self.payers.is_empty() {
+            return true;
+        }
+
+        let mut ok = false;
+        for payer in &self.payers {
+            match self.rpc_client.get_balance(payer).await {
+                Ok(balance) => {
+                    PAYER_BALANCE
+                        .with_label_values(&[&payer.to_string()])
+                        .set(balance as i64);
+                    ok |= balance >= self.min_payer_balance;
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "Health: failed to get balance of payer {}: {:?}",
+                        payer,
+                        err
+                    );
+                }
+            }
+        }
+
+        if !ok {
+            tracing::warn!(
+                "Health: all payers have less than {} lamports",
+                self.min_payer_balance
+            );
+        }
+
+        ok
+    }
+
+    /// Reasons the proxy is not ready, empty if it is ready
+    fn not_ready(&self) -> Vec<&'static str> {
+        let mut reasons = vec![];
+        if !self.clock_ok.load(Ordering::Relaxed) {
+            reasons.push("Solana clock is stale");
+        }
+        if !self.payers_ok.load(Ordering::Relaxed) {
+            reasons.push("payers are out of lamports");
+        }
+
+        reasons
+    }
+}
+
+/// HTTP service serving liveness, readiness and metrics
+#[derive(Clone)]
+struct HealthService(Arc<Health>);
+
+impl<B> tower::Service<HttpRequest<B>> for HealthService {
+    type Response = HttpResponse;
+    type Error = BoxError;
+    type Future = Ready<Result<HttpResponse, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: HttpRequest<B>) -> Self::Future {
+        let response = match request.uri().path() {
+            "/health" => response(200, "OK".to_string()),
+            "/ready" => match self.0.not_ready() {
+                reasons if reasons.is_empty() => response(200, "OK".to_string()),
+                reasons => response(503, reasons.join("\n")),
+            },
+            "/metrics" => response(200, metrics::render()),
+            _ => response(404, "Not found".to_string()),
+        };
+
+        std::future::ready(Ok(response))
+    }
+}
+
+fn response(status: u16, body: String) -> HttpResponse {
+    HttpResponse::builder()
+        .status(status)
+        .header("content-type", "text/plain; charset=utf-8")
+        .body(HttpBody::from(body))
+        .expect("Unable to build the response")
+}
+
+/// Start the HTTP server serving /health, /ready and /metrics
+pub async fn start_http_server(health: Arc<Health>, host: SocketAddr) -> anyhow::Result<()> {
+    tracing::info!("Starting the health server at {host}");
+
+    let listener = TcpListener::bind(host).await?;
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, HealthService(health.clone())));
+                }
+                Err(err) => tracing::error!("Failed to accept the connection: {:?}", err),
+            }
+        }
+    });
+
+    Ok(())
+}
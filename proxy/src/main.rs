@@ -1,7 +1,17 @@
 mod api;
+mod cache;
+mod chain;
 mod cli;
 mod config;
+mod filters;
+mod health;
+mod metrics;
 mod proxy;
+mod rate_limit;
+mod solana_providers;
+mod state;
+mod subscriptions;
+mod tracer;
 
 use self::cli::Cli;
 use anyhow::anyhow;
@@ -0,0 +1,192 @@
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::server::MethodResponse;
+use jsonrpsee::types::error::METHOD_NOT_FOUND_CODE;
+use jsonrpsee::types::Request;
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, register_int_gauge_vec,
+    HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder,
+};
+use rpc_error::EXECUTION_REVERTED_CODE;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::LazyLock;
+use std::time::Instant;
+
+pub static REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "proxy_requests_total",
+        "Number of RPC requests",
+        &["method"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "proxy_request_duration_seconds",
+        "Duration of RPC requests",
+        &["method"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static REQUEST_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "proxy_request_errors_total",
+        "Number of RPC requests failed with the error code",
+        &["method", "code"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static API_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "proxy_api_errors_total",
+        "Number of RPC requests failed with the error reason",
+        &["method", "reason"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static PAYER_BALANCE: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "proxy_payer_balance_lamports",
+        "Balance of the payer",
+        &["payer"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static SOLANA_CLOCK_LAG: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "proxy_solana_clock_lag_seconds",
+        "Lag of the Solana clock behind the local time"
+    )
+    .expect("metric can be registered")
+});
+
+/// Render all metrics in the Prometheus text format
+pub fn render() -> String {
+    TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .unwrap_or_else(|err| {
+            tracing::warn!("Failed to encode metrics: {:?}", err);
+            String::new()
+        })
+}
+
+/// RPC middleware collecting request metrics
+#[derive(Clone)]
+pub struct MetricsService<S> {
+    service: S,
+}
+
+impl<S> MetricsService<S> {
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for MetricsService<S>
+where
+    S: RpcServiceT<'a>,
+    S::Future: 'a,
+{
+    type Future = Pin<Box<dyn Future<Output = MethodResponse> + Send + 'a>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        let method = request.method_name().to_string();
+        let response = self.service.call(request);
+
+        Box::pin(async move {
+            let started = Instant::now();
+            let response = response.await;
+
+            // Do not create series for arbitrary method names
+            let method = match response.as_error_code() {
+                Some(METHOD_NOT_FOUND_CODE) => "unknown",
+                _ => method.as_str(),
+            };
+            REQUESTS.with_label_values(&[method]).inc();
+            REQUEST_DURATION
+                .with_label_values(&[method])
+                .observe(started.elapsed().as_secs_f64());
+            if let Some(code) = response.as_error_code() {
+                REQUEST_ERRORS
+                    .with_label_values(&[method, &code.to_string()])
+                    .inc();
+                API_ERRORS
+                    .with_label_values(&[method, &error_reason(code, &response)])
+                    .inc();
+            }
+
+            response
+        })
+    }
+}
+
+/// Reason of the error taken from the error data, see [rpc_error::error_object]
+fn error_reason(code: i32, response: &MethodResponse) -> String {
+    #[derive(serde::Deserialize)]
+    struct Response {
+        error: ErrorObject,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct ErrorObject {
+        data: Option<serde_json::Value>,
+    }
+
+    let reason = serde_json::from_str::<Response>(response.as_result())
+        .ok()
+        .and_then(|response| response.error.data)
+        .and_then(|data| data.get("reason")?.as_str().map(str::to_string));
+
+    match reason {
+        Some(reason) => reason,
+        None if code == EXECUTION_REVERTED_CODE => "execution_reverted".to_string(),
+        None => "other".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::types::{ErrorObjectOwned, Id};
+    use rpc_error::{error_object, LIMIT_EXCEEDED_CODE};
+
+    fn error_response(err: ErrorObjectOwned) -> MethodResponse {
+        MethodResponse::error(Id::Number(1), err)
+    }
+
+    #[test]
+    fn error_reason_is_taken_from_error_data() {
+        let limited = error_response(error_object(
+            LIMIT_EXCEEDED_CODE,
+            "rate limit exceeded".to_string(),
+            "proxy",
+            "limit_exceeded",
+        ));
+        assert_eq!(
+            error_reason(LIMIT_EXCEEDED_CODE, &limited),
+            "limit_exceeded"
+        );
+
+        let reverted = error_response(ErrorObjectOwned::owned(
+            EXECUTION_REVERTED_CODE,
+            "execution reverted",
+            Some("0x08c379a0"),
+        ));
+        assert_eq!(
+            error_reason(EXECUTION_REVERTED_CODE, &reverted),
+            "execution_reverted"
+        );
+
+        let invalid = error_response(ErrorObjectOwned::owned(
+            -32602,
+            "invalid params",
+            None::<()>,
+        ));
+        assert_eq!(error_reason(-32602, &invalid), "other");
+    }
+}
@@ -1,38 +1,120 @@
-use crate::api::EthServer;
+use crate::api::lookup::Lookup;
+use crate::api::{DebugServer, EthLookupServer, EthServer};
+use crate::cache::{CachedService, ResponseCache};
+use crate::filters::Filters;
+use crate::metrics::MetricsService;
+use crate::rate_limit::{RateLimitedHttpService, RateLimitedService, RateLimiter};
+use crate::state::StateClients;
+use crate::subscriptions::Subscriptions;
 use anyhow::Context;
-use jsonrpsee::server::{ServerBuilder, ServerHandle};
-use jsonrpsee::RpcModule;
+use jsonrpsee::server::{
+    serve_with_graceful_shutdown, stop_channel, RpcServiceBuilder, ServerBuilder, ServerHandle,
+};
+use jsonrpsee::{Methods, RpcModule};
 
 use rome_sdk::rome_evm_client::RomeEVMClient;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Maximum size of the RPC response, also applied to the responses served from the cache
+const MAX_RESPONSE_BODY_SIZE: u32 = 10 * 1024 * 1024;
 
 #[derive(Clone)]
 pub struct Proxy {
     pub rome_evm_client: Arc<RomeEVMClient>,
+    pub state: Arc<StateClients>,
+    pub filters: Arc<Filters>,
+    pub subscriptions: Arc<Subscriptions>,
+    pub cache: Option<Arc<ResponseCache>>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl Proxy {
     /// Create a new instance of the [Proxy]
-    pub fn new(rome_evm_client: Arc<RomeEVMClient>) -> Self {
-        Self { rome_evm_client }
+    pub fn new(
+        rome_evm_client: Arc<RomeEVMClient>,
+        state: StateClients,
+        filters: Filters,
+        subscriptions: Arc<Subscriptions>,
+        cache: Option<Arc<ResponseCache>>,
+        rate_limiter: Option<Arc<RateLimiter>>,
+    ) -> Self {
+        Self {
+            rome_evm_client,
+            state: Arc::new(state),
+            filters: Arc::new(filters),
+            subscriptions,
+            cache,
+            rate_limiter,
+        }
     }
 
-    /// Start the RPC server
+    /// Start the RPC server serving both HTTP and WebSocket connections
     pub async fn start_rpc_server(self, host: SocketAddr) -> anyhow::Result<ServerHandle> {
         tracing::info!("Starting the RPC server at {host}");
 
-        let rpc = ServerBuilder::default()
-            .build(host)
+        let listener = TcpListener::bind(host)
             .await
             .context("Unable to start the RPC server")?;
 
+        let cache = self.cache.clone();
+        let rate_limiter = self.rate_limiter.clone();
+        let service_builder = ServerBuilder::default()
+            .set_rpc_middleware(
+                RpcServiceBuilder::new()
+                    .layer_fn(MetricsService::new)
+                    .layer_fn(move |service| RateLimitedService::new(service, rate_limiter.clone()))
+                    .layer_fn(move |service| {
+                        CachedService::new(service, cache.clone(), MAX_RESPONSE_BODY_SIZE)
+                    }),
+            )
+            .max_response_body_size(MAX_RESPONSE_BODY_SIZE)
+            .max_subscriptions_per_connection(self.subscriptions.max_subscriptions_per_connection)
+            .to_service_builder();
+
         let mut module = RpcModule::new(());
 
         // merge the rpc
-        module.merge(EthServer::into_rpc(self)).unwrap();
+        module.merge(EthServer::into_rpc(self.clone())).unwrap();
+        module
+            .merge(EthLookupServer::into_rpc(Lookup::new(
+                self.rome_evm_client.clone(),
+            )))
+            .unwrap();
+        module.merge(DebugServer::into_rpc(self.clone())).unwrap();
+        let methods = Methods::from(module);
+
+        // accept connections, the remote address identifies the client for rate limiting
+        let (stop_handle, server_handle) = stop_channel();
+        tokio::spawn(async move {
+            loop {
+                let (stream, remote_addr) = tokio::select! {
+                    res = listener.accept() => match res {
+                        Ok(conn) => conn,
+                        Err(err) => {
+                            tracing::error!("Failed to accept the connection: {:?}", err);
+                            continue;
+                        }
+                    },
+                    _ = stop_handle.clone().shutdown() => break,
+                };
+
+                let service = RateLimitedHttpService::new(
+                    service_builder
+                        .clone()
+                        .build(methods.clone(), stop_handle.clone()),
+                    self.rate_limiter.clone(),
+                    remote_addr.ip(),
+                );
+                tokio::spawn(serve_with_graceful_shutdown(
+                    stream,
+                    service,
+                    stop_handle.clone().shutdown(),
+                ));
+            }
+        });
 
-        // start and return the rpc handle
-        Ok(rpc.start(module))
+        Ok(server_handle)
     }
 }
@@ -0,0 +1,474 @@
+use crate::api::ApiError;
+use jsonrpsee::core::BoxError;
+use jsonrpsee::server::middleware::rpc::RpcServiceT;
+use jsonrpsee::server::{HttpRequest, HttpResponse, MethodResponse};
+use jsonrpsee::types::{ErrorObjectOwned, Request};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+const API_KEY_HEADER: &str = "x-api-key";
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+const DEFAULT_METHOD_COST: u32 = 1;
+const DEFAULT_MAX_CLIENTS: usize = 100_000;
+const DEFAULT_METHOD_COSTS: &[(&str, u32)] = &[
+    ("eth_sendRawTransaction", 10),
+    ("eth_estimateGas", 5),
+    ("eth_call", 2),
+    ("eth_getLogs", 5),
+    ("eth_getFilterLogs", 5),
+    ("debug_traceTransaction", 10),
+    ("debug_traceCall", 10),
+];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct Quota {
+    /// Number of request units replenished every second
+    pub units_per_sec: u32,
+    /// Maximum number of request units which can be spent at once, `units_per_sec` by default
+    pub burst: Option<u32>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct RateLimitConfig {
+    /// Quota of every client IP address, not limited if not set
+    pub per_ip: Option<Quota>,
+    /// Quotas of the API keys passed in the `x-api-key` header.
+    /// Requests with a known API key are not limited per IP.
+    #[serde(default)]
+    pub api_keys: HashMap<String, Quota>,
+    /// Cost of the methods in request units, overrides the default costs
+    pub method_costs: Option<HashMap<String, u32>>,
+    /// Take the client IP address from the `x-forwarded-for` header set by the load balancer
+    pub trust_forwarded_for: Option<bool>,
+    /// Maximum number of tracked clients
+    pub max_clients: Option<usize>,
+}
+
+/// Client the request is accounted to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Client {
+    ApiKey(String),
+    Ip(IpAddr),
+}
+
+struct Bucket {
+    units: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, quota: &Quota) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.units = (self.units + elapsed * quota.units_per_sec as f64).min(burst(quota));
+        self.updated = now;
+    }
+}
+
+fn burst(quota: &Quota) -> f64 {
+    quota.burst.unwrap_or(quota.units_per_sec) as f64
+}
+
+/// Token bucket rate limiter with weighted method costs
+pub struct RateLimiter {
+    per_ip: Option<Quota>,
+    api_keys: HashMap<String, Quota>,
+    method_costs: HashMap<String, u32>,
+    trust_forwarded_for: bool,
+    max_clients: usize,
+    buckets: Mutex<HashMap<Client, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let mut method_costs = DEFAULT_METHOD_COSTS
+            .iter()
+            .map(|(method, cost)| (method.to_string(), *cost))
+            .collect::<HashMap<_, _>>();
+        method_costs.extend(config.method_costs.clone().unwrap_or_default());
+
+        Self {
+            per_ip: config.per_ip.clone(),
+            api_keys: config.api_keys.clone(),
+            method_costs,
+            trust_forwarded_for: config.trust_forwarded_for.unwrap_or_default(),
+            max_clients: config.max_clients.unwrap_or(DEFAULT_MAX_CLIENTS),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Identify the client by the API key or by the IP address
+    pub fn client(
+        &self,
+        remote_ip: IpAddr,
+        api_key: Option<&str>,
+        forwarded_for: Option<&str>,
+    ) -> Client {
+        if let Some(api_key) = api_key.filter(|key| self.api_keys.contains_key(*key)) {
+            return Client::ApiKey(api_key.to_string());
+        }
+
+        let forwarded_ip = forwarded_for
+            .filter(|_| self.trust_forwarded_for)
+            .and_then(|forwarded_for| forwarded_for.split(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+
+        Client::Ip(forwarded_ip.unwrap_or(remote_ip))
+    }
+
+    fn quota(&self, client: &Client) -> Option<&Quota> {
+        match client {
+            Client::ApiKey(api_key) => self.api_keys.get(api_key),
+            Client::Ip(_) => self.per_ip.as_ref(),
+        }
+    }
+
+    fn cost(&self, method: &str) -> u32 {
+        self.method_costs
+            .get(method)
+            .copied()
+            .unwrap_or(DEFAULT_METHOD_COST)
+    }
+
+    /// Spend the request units of the client, returns false if the client is out of quota
+    fn try_acquire(&self, client: &Client, cost: u32) -> bool {
+        self.with_bucket(client, |bucket| {
+            let cost = cost as f64;
+            if bucket.units < cost {
+                return false;
+            }
+
+            bucket.units -= cost;
+            true
+        })
+    }
+
+    fn with_bucket(&self, client: &Client, f: impl FnOnce(&mut Bucket) -> bool) -> bool {
+        let Some(quota) = self.quota(client) else {
+            return f(&mut Bucket {
+                units: f64::MAX,
+                updated: Instant::now(),
+            });
+        };
+
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        if buckets.len() >= self.max_clients && !buckets.contains_key(client) {
+            // Full buckets are equal to the new ones and can be dropped
+            buckets.retain(|client, bucket| match self.quota(client) {
+                Some(quota) => {
+                    bucket.refill(quota);
+                    bucket.units < burst(quota)
+                }
+                None => false,
+            });
+        }
+
+        let bucket = buckets.entry(client.clone()).or_insert_with(|| Bucket {
+            units: burst(quota),
+            updated: Instant::now(),
+        });
+        bucket.refill(quota);
+        f(bucket)
+    }
+}
+
+/// HTTP service identifying the client the RPC requests are accounted to
+#[derive(Clone)]
+pub struct RateLimitedHttpService<S> {
+    service: S,
+    limiter: Option<Arc<RateLimiter>>,
+    remote_ip: IpAddr,
+}
+
+impl<S> RateLimitedHttpService<S> {
+    pub fn new(service: S, limiter: Option<Arc<RateLimiter>>, remote_ip: IpAddr) -> Self {
+        Self {
+            service,
+            limiter,
+            remote_ip,
+        }
+    }
+}
+
+impl<S, B> tower::Service<HttpRequest<B>> for RateLimitedHttpService<S>
+where
+    S: tower::Service<HttpRequest<B>, Response = HttpResponse, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = HttpResponse;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<HttpResponse, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: HttpRequest<B>) -> Self::Future {
+        if let Some(limiter) = &self.limiter {
+            let header = |name: &str| {
+                request
+                    .headers()
+                    .get(name)
+                    .and_then(|value| value.to_str().ok())
+            };
+            let client = limiter.client(
+                self.remote_ip,
+                header(API_KEY_HEADER),
+                header(FORWARDED_FOR_HEADER),
+            );
+            request.extensions_mut().insert(client);
+        }
+
+        Box::pin(self.service.call(request))
+    }
+}
+
+/// RPC middleware charging the client with the cost of the method.
+/// Calls exceeding the quota fail with the JSON-RPC limit exceeded error.
+#[derive(Clone)]
+pub struct RateLimitedService<S> {
+    service: S,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl<S> RateLimitedService<S> {
+    pub fn new(service: S, limiter: Option<Arc<RateLimiter>>) -> Self {
+        Self { service, limiter }
+    }
+}
+
+impl<'a, S> RpcServiceT<'a> for RateLimitedService<S>
+where
+    S: RpcServiceT<'a>,
+    S::Future: 'a,
+{
+    type Future = Pin<Box<dyn Future<Output = MethodResponse> + Send + 'a>>;
+
+    fn call(&self, request: Request<'a>) -> Self::Future {
+        if let (Some(limiter), Some(client)) = (&self.limiter, request.extensions().get::<Client>())
+        {
+            let cost = limiter.cost(request.method_name());
+            if !limiter.try_acquire(client, cost) {
+                tracing::debug!(
+                    "Rate limit exceeded by {:?} calling {}",
+                    client,
+                    request.method_name()
+                );
+                let id = request.id().into_owned();
+                return Box::pin(async move { MethodResponse::error(id, rate_limit_error()) });
+            }
+        }
+
+        Box::pin(self.service.call(request))
+    }
+}
+
+fn rate_limit_error() -> ErrorObjectOwned {
+    ApiError::LimitExceeded("rate limit exceeded".to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonrpsee::server::ResponsePayload;
+    use jsonrpsee::types::Id;
+    use std::borrow::Cow;
+    use std::future::Ready;
+    use std::time::Duration;
+
+    const REMOTE_IP: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1));
+
+    fn quota(units_per_sec: u32, burst: Option<u32>) -> Quota {
+        Quota {
+            units_per_sec,
+            burst,
+        }
+    }
+
+    fn limiter(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            per_ip: Some(quota(10, Some(20))),
+            ..config
+        })
+    }
+
+    /// Move the last refill of the bucket of the client to the past
+    fn rewind(limiter: &RateLimiter, client: &Client, elapsed: Duration) {
+        let mut buckets = limiter.buckets.lock().unwrap();
+        let bucket = buckets.get_mut(client).unwrap();
+        bucket.updated -= elapsed;
+    }
+
+    #[test]
+    fn bucket_refills_up_to_burst() {
+        let limiter = limiter(RateLimitConfig::default());
+        let client = Client::Ip(REMOTE_IP);
+
+        assert!(limiter.try_acquire(&client, 20));
+        assert!(!limiter.try_acquire(&client, 1));
+
+        rewind(&limiter, &client, Duration::from_millis(500));
+        assert!(limiter.try_acquire(&client, 5));
+        assert!(!limiter.try_acquire(&client, 1));
+
+        rewind(&limiter, &client, Duration::from_secs(60));
+        assert!(limiter.try_acquire(&client, 20));
+        assert!(!limiter.try_acquire(&client, 1));
+    }
+
+    #[test]
+    fn burst_defaults_to_rate() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            per_ip: Some(quota(10, None)),
+            ..Default::default()
+        });
+        let client = Client::Ip(REMOTE_IP);
+
+        assert!(!limiter.try_acquire(&client, 11));
+        assert!(limiter.try_acquire(&client, 10));
+    }
+
+    #[test]
+    fn clients_have_own_buckets() {
+        let limiter = limiter(RateLimitConfig {
+            api_keys: HashMap::from([("key".to_string(), quota(100, None))]),
+            ..Default::default()
+        });
+        let ip = Client::Ip(REMOTE_IP);
+        let other_ip = Client::Ip("10.0.0.2".parse().unwrap());
+        let api_key = Client::ApiKey("key".to_string());
+
+        assert!(limiter.try_acquire(&ip, 20));
+        assert!(!limiter.try_acquire(&ip, 1));
+        assert!(limiter.try_acquire(&other_ip, 20));
+        assert!(limiter.try_acquire(&api_key, 100));
+        assert!(!limiter.try_acquire(&api_key, 1));
+    }
+
+    #[test]
+    fn unlimited_without_quota() {
+        let limiter = RateLimiter::new(&RateLimitConfig::default());
+        let client = Client::Ip(REMOTE_IP);
+
+        for _ in 0..1000 {
+            assert!(limiter.try_acquire(&client, 10));
+        }
+        assert!(limiter.buckets.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn full_buckets_are_dropped_over_max_clients() {
+        let limiter = limiter(RateLimitConfig {
+            max_clients: Some(2),
+            ..Default::default()
+        });
+        let clients = (1..=3)
+            .map(|i| Client::Ip(IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, i))))
+            .collect::<Vec<_>>();
+
+        assert!(limiter.try_acquire(&clients[0], 20));
+        assert!(limiter.try_acquire(&clients[1], 0));
+        assert!(limiter.try_acquire(&clients[2], 0));
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.contains_key(&clients[0]));
+        assert!(!buckets.contains_key(&clients[1]));
+        assert!(buckets.contains_key(&clients[2]));
+    }
+
+    #[test]
+    fn method_costs() {
+        let limiter = limiter(RateLimitConfig {
+            method_costs: Some(HashMap::from([
+                ("eth_call".to_string(), 3),
+                ("eth_getBalance".to_string(), 4),
+            ])),
+            ..Default::default()
+        });
+
+        assert_eq!(limiter.cost("eth_sendRawTransaction"), 10);
+        assert_eq!(limiter.cost("debug_traceCall"), 10);
+        assert_eq!(limiter.cost("eth_call"), 3);
+        assert_eq!(limiter.cost("eth_getBalance"), 4);
+        assert_eq!(limiter.cost("eth_chainId"), DEFAULT_METHOD_COST);
+    }
+
+    #[test]
+    fn forwarded_for_is_trusted_if_enabled() {
+        let forwarded_for = Some("192.168.1.7, 10.0.0.254");
+        let forwarded_ip = Client::Ip("192.168.1.7".parse().unwrap());
+
+        let untrusted = limiter(RateLimitConfig::default());
+        assert_eq!(
+            untrusted.client(REMOTE_IP, None, forwarded_for),
+            Client::Ip(REMOTE_IP)
+        );
+
+        let trusted = limiter(RateLimitConfig {
+            trust_forwarded_for: Some(true),
+            ..Default::default()
+        });
+        assert_eq!(trusted.client(REMOTE_IP, None, forwarded_for), forwarded_ip);
+        assert_eq!(
+            trusted.client(REMOTE_IP, None, Some("unknown")),
+            Client::Ip(REMOTE_IP)
+        );
+        assert_eq!(trusted.client(REMOTE_IP, None, None), Client::Ip(REMOTE_IP));
+    }
+
+    #[test]
+    fn known_api_keys_identify_the_client() {
+        let limiter = limiter(RateLimitConfig {
+            api_keys: HashMap::from([("key".to_string(), quota(100, None))]),
+            trust_forwarded_for: Some(true),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            limiter.client(REMOTE_IP, Some("key"), Some("192.168.1.7")),
+            Client::ApiKey("key".to_string())
+        );
+        assert_eq!(
+            limiter.client(REMOTE_IP, Some("unknown"), None),
+            Client::Ip(REMOTE_IP)
+        );
+    }
+
+    #[derive(Clone)]
+    struct StubService;
+
+    impl<'a> RpcServiceT<'a> for StubService {
+        type Future = Ready<MethodResponse>;
+
+        fn call(&self, request: Request<'a>) -> Self::Future {
+            std::future::ready(MethodResponse::response(
+                request.id().into_owned(),
+                ResponsePayload::success("0x1"),
+                usize::MAX,
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn calls_over_quota_fail_with_limit_exceeded() {
+        let limiter = Arc::new(limiter(RateLimitConfig::default()));
+        let service = RateLimitedService::new(StubService, Some(limiter));
+        let call = |method: &'static str| {
+            let mut request = Request::new(Cow::Borrowed(method), None, Id::Number(1));
+            request.extensions_mut().insert(Client::Ip(REMOTE_IP));
+            service.call(request)
+        };
+
+        assert!(call("eth_sendRawTransaction").await.is_success());
+        assert!(call("eth_sendRawTransaction").await.is_success());
+
+        let response = call("eth_sendRawTransaction").await;
+        assert_eq!(response.as_error_code(), Some(-32005));
+        assert!(!call("eth_chainId").await.is_success());
+    }
+}
@@ -0,0 +1,595 @@
+use async_trait::async_trait;
+use solana_client::client_error::{ClientError, ClientErrorKind, Result as ClientResult};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::RpcClientConfig;
+use solana_client::rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY;
+use solana_client::rpc_request::{RpcError, RpcRequest};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_rpc_client::http_sender::HttpSender;
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const DEFAULT_HEALTH_CHECK_INTERVAL_MS: u64 = 5_000;
+const DEFAULT_MAX_FAILURES: u32 = 3;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct SolanaFailoverConfig {
+    /// Additional Solana RPC providers used on failures of the primary one
+    pub rpc_urls: Vec<url::Url>,
+    /// Interval of getHealth requests sent to every provider
+    pub health_check_interval_ms: Option<u64>,
+    /// Number of consecutive failures after which a provider is considered unhealthy
+    pub max_failures: Option<u32>,
+    /// If set, read requests are sent to the second provider
+    /// when the first one has not responded within this delay
+    pub hedge_delay_ms: Option<u64>,
+}
+
+/// Health of the Solana RPC provider reported by the admin API
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderHealth {
+    pub url: String,
+    pub healthy: bool,
+    pub latency_ms: f64,
+    pub consecutive_failures: u32,
+    pub requests: u64,
+    pub errors: u64,
+}
+
+struct Provider {
+    url: String,
+    sender: Box<dyn RpcSender + Send + Sync>,
+    healthy: AtomicBool,
+    latency_us: AtomicU64,
+    consecutive_failures: AtomicU32,
+    requests: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl Provider {
+    fn new(url: String, sender: Box<dyn RpcSender + Send + Sync>) -> Self {
+        Self {
+            sender,
+            url,
+            healthy: AtomicBool::new(true),
+            latency_us: AtomicU64::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+        max_failures: u32,
+    ) -> ClientResult<serde_json::Value> {
+        let started = Instant::now();
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let result = self.sender.send(request, params).await;
+
+        match &result {
+            Err(err) if is_provider_error(err) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+                if failures >= max_failures && self.healthy.swap(false, Ordering::Relaxed) {
+                    tracing::warn!("Solana RPC provider {} is unhealthy: {:?}", self.url, err);
+                }
+            }
+            _ => {
+                self.record_latency(started.elapsed());
+                self.consecutive_failures.store(0, Ordering::Relaxed);
+                if !self.healthy.swap(true, Ordering::Relaxed) {
+                    tracing::info!("Solana RPC provider {} is healthy again", self.url);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        let sample = latency.as_micros() as u64;
+        let _ = self
+            .latency_us
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                // Exponentially weighted moving average
+                Some(if current == 0 {
+                    sample
+                } else {
+                    (current * 7 + sample) / 8
+                })
+            });
+    }
+
+    fn health(&self) -> ProviderHealth {
+        ProviderHealth {
+            url: self.url.clone(),
+            healthy: self.healthy.load(Ordering::Relaxed),
+            latency_ms: self.latency_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Set of Solana RPC providers with health checks and latency-based selection
+pub struct SolanaProviders {
+    providers: Vec<Provider>,
+    health_check_interval: Duration,
+    max_failures: u32,
+    hedge_delay: Option<Duration>,
+}
+
+impl SolanaProviders {
+    pub fn new(primary: &url::Url, config: &SolanaFailoverConfig) -> Self {
+        let senders = std::iter::once(primary)
+            .chain(config.rpc_urls.iter())
+            .map(|url| {
+                let sender: Box<dyn RpcSender + Send + Sync> =
+                    Box::new(HttpSender::new(url.to_string()));
+                (url.to_string(), sender)
+            })
+            .collect();
+
+        Self::with_senders(senders, config)
+    }
+
+    fn with_senders(
+        senders: Vec<(String, Box<dyn RpcSender + Send + Sync>)>,
+        config: &SolanaFailoverConfig,
+    ) -> Self {
+        let providers = senders
+            .into_iter()
+            .map(|(url, sender)| Provider::new(url, sender))
+            .collect();
+
+        Self {
+            providers,
+            health_check_interval: Duration::from_millis(
+                config
+                    .health_check_interval_ms
+                    .unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL_MS),
+            ),
+            max_failures: config.max_failures.unwrap_or(DEFAULT_MAX_FAILURES),
+            hedge_delay: config.hedge_delay_ms.map(Duration::from_millis),
+        }
+    }
+
+    /// Create an RPC client sending requests through the providers
+    pub fn rpc_client(self: &Arc<Self>, commitment: CommitmentLevel) -> RpcClient {
+        RpcClient::new_sender(
+            FailoverSender(self.clone()),
+            RpcClientConfig::with_commitment(CommitmentConfig { commitment }),
+        )
+    }
+
+    pub fn health(&self) -> Vec<ProviderHealth> {
+        self.providers.iter().map(Provider::health).collect()
+    }
+
+    /// Periodically check the health of every provider
+    pub async fn start_health_checks(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(self.health_check_interval);
+        loop {
+            interval.tick().await;
+            for provider in &self.providers {
+                if let Err(err) = provider
+                    .send(
+                        RpcRequest::GetHealth,
+                        serde_json::Value::Null,
+                        self.max_failures,
+                    )
+                    .await
+                {
+                    tracing::debug!(
+                        "Solana RPC provider {} health check: {:?}",
+                        provider.url,
+                        err
+                    );
+                }
+            }
+        }
+    }
+
+    /// Providers in the order of preference: healthy ones first, faster ones first
+    fn ordered(&self) -> Vec<&Provider> {
+        let mut providers = self.providers.iter().collect::<Vec<_>>();
+        providers.sort_by_key(|provider| {
+            (
+                !provider.healthy.load(Ordering::Relaxed),
+                provider.latency_us.load(Ordering::Relaxed),
+            )
+        });
+
+        providers
+    }
+
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        let providers = self.ordered();
+        let mut providers = providers.iter();
+        let Some(first) = providers.next() else {
+            return Err(ClientErrorKind::Custom("No Solana RPC providers".to_string()).into());
+        };
+
+        let second = providers.as_slice().first().copied();
+        let mut result = match (self.hedge_delay, is_read_request(request), second) {
+            (Some(delay), true, Some(second)) => {
+                providers.next();
+                self.hedged(first, second, delay, request, params.clone())
+                    .await
+            }
+            _ => first.send(request, params.clone(), self.max_failures).await,
+        };
+
+        // Fail over to the next providers
+        for provider in providers {
+            match &result {
+                Err(err) if is_provider_error(err) => {
+                    tracing::warn!(
+                        "Solana RPC request {} failed, retrying with {}: {:?}",
+                        request,
+                        provider.url,
+                        err
+                    );
+                    result = provider
+                        .send(request, params.clone(), self.max_failures)
+                        .await;
+                }
+                _ => break,
+            }
+        }
+
+        result
+    }
+
+    /// Send the request to the first provider and, if it is slow, to the second one too.
+    /// The first successful response wins.
+    async fn hedged(
+        &self,
+        first: &Provider,
+        second: &Provider,
+        delay: Duration,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        let primary = first.send(request, params.clone(), self.max_failures);
+        tokio::pin!(primary);
+
+        tokio::select! {
+            res = &mut primary => {
+                match res {
+                    Err(err) if is_provider_error(&err) => {
+                        return second.send(request, params, self.max_failures).await
+                    }
+                    res => return res,
+                }
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
+
+        let secondary = second.send(request, params, self.max_failures);
+        tokio::pin!(secondary);
+
+        tokio::select! {
+            res = &mut primary => match res {
+                Err(err) if is_provider_error(&err) => secondary.await,
+                res => res,
+            },
+            res = &mut secondary => match res {
+                Err(err) if is_provider_error(&err) => primary.await,
+                res => res,
+            },
+        }
+    }
+}
+
+/// [RpcSender] distributing requests across [SolanaProviders]
+struct FailoverSender(Arc<SolanaProviders>);
+
+#[async_trait]
+impl RpcSender for FailoverSender {
+    async fn send(
+        &self,
+        request: RpcRequest,
+        params: serde_json::Value,
+    ) -> ClientResult<serde_json::Value> {
+        self.0.send(request, params).await
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        self.0
+            .providers
+            .iter()
+            .map(|provider| provider.sender.get_transport_stats())
+            .fold(RpcTransportStats::default(), |mut total, stats| {
+                total.request_count += stats.request_count;
+                total.elapsed_time += stats.elapsed_time;
+                total.rate_limited_time += stats.rate_limited_time;
+                total
+            })
+    }
+
+    fn url(&self) -> String {
+        self.0
+            .ordered()
+            .first()
+            .map(|provider| provider.url.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Errors caused by the provider itself rather than by the request
+fn is_provider_error(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_) => true,
+        ClientErrorKind::RpcError(RpcError::RpcRequestError(_)) => true,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+        }
+        _ => false,
+    }
+}
+
+/// Requests which can be safely sent to several providers at once
+fn is_read_request(request: RpcRequest) -> bool {
+    !matches!(
+        request,
+        RpcRequest::SendTransaction | RpcRequest::RequestAirdrop
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::rpc_request::RpcResponseErrorData;
+
+    const PROVIDER_ERROR: i64 = -1;
+    const REQUEST_ERROR: i64 = -2;
+
+    /// Provider answering after a delay with a fixed result, negative results are errors
+    struct StubSender {
+        delay: Duration,
+        result: Arc<AtomicU64>,
+        requests: Arc<AtomicU64>,
+    }
+
+    #[async_trait]
+    impl RpcSender for StubSender {
+        async fn send(
+            &self,
+            _request: RpcRequest,
+            _params: serde_json::Value,
+        ) -> ClientResult<serde_json::Value> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(self.delay).await;
+            match self.result.load(Ordering::Relaxed) as i64 {
+                PROVIDER_ERROR => {
+                    Err(ClientErrorKind::Io(std::io::Error::other("connection refused")).into())
+                }
+                REQUEST_ERROR => Err(ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                    code: -32602,
+                    message: "invalid params".to_string(),
+                    data: RpcResponseErrorData::Empty,
+                })
+                .into()),
+                result => Ok(result.into()),
+            }
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            String::new()
+        }
+    }
+
+    struct Stub {
+        result: Arc<AtomicU64>,
+        requests: Arc<AtomicU64>,
+    }
+
+    impl Stub {
+        fn set(&self, result: i64) {
+            self.result.store(result as u64, Ordering::Relaxed);
+        }
+
+        fn requests(&self) -> u64 {
+            self.requests.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Providers answering with their index after the delay in milliseconds
+    fn stub_providers(
+        delays_ms: &[u64],
+        config: SolanaFailoverConfig,
+    ) -> (SolanaProviders, Vec<Stub>) {
+        let mut senders = vec![];
+        let mut stubs = vec![];
+        for (index, delay) in delays_ms.iter().enumerate() {
+            let stub = Stub {
+                result: Arc::new(AtomicU64::new(index as u64)),
+                requests: Arc::new(AtomicU64::new(0)),
+            };
+            let sender: Box<dyn RpcSender + Send + Sync> = Box::new(StubSender {
+                delay: Duration::from_millis(*delay),
+                result: stub.result.clone(),
+                requests: stub.requests.clone(),
+            });
+            senders.push((format!("http://provider-{index}"), sender));
+            stubs.push(stub);
+        }
+
+        (SolanaProviders::with_senders(senders, &config), stubs)
+    }
+
+    async fn get_slot(providers: &SolanaProviders) -> ClientResult<serde_json::Value> {
+        providers
+            .send(RpcRequest::GetSlot, serde_json::Value::Null)
+            .await
+    }
+
+    fn urls(providers: &SolanaProviders) -> Vec<String> {
+        providers
+            .ordered()
+            .iter()
+            .map(|provider| provider.url.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn fails_over_in_order() {
+        let (providers, stubs) = stub_providers(&[0, 0, 0], SolanaFailoverConfig::default());
+        stubs[0].set(PROVIDER_ERROR);
+        stubs[1].set(PROVIDER_ERROR);
+
+        assert_eq!(get_slot(&providers).await.unwrap(), 2);
+        assert_eq!(
+            stubs.iter().map(Stub::requests).collect::<Vec<_>>(),
+            vec![1, 1, 1]
+        );
+
+        let health = providers.health();
+        assert_eq!(health[0].errors, 1);
+        assert_eq!(health[0].consecutive_failures, 1);
+        assert_eq!(health[2].errors, 0);
+    }
+
+    #[tokio::test]
+    async fn request_errors_are_not_retried() {
+        let (providers, stubs) = stub_providers(&[0, 0], SolanaFailoverConfig::default());
+        stubs[0].set(REQUEST_ERROR);
+
+        assert!(get_slot(&providers).await.is_err());
+        assert_eq!(stubs[1].requests(), 0);
+        assert_eq!(providers.health()[0].errors, 0);
+        assert!(providers.health()[0].healthy);
+    }
+
+    #[tokio::test]
+    async fn unhealthy_providers_go_last() {
+        let (providers, stubs) = stub_providers(
+            &[0, 0],
+            SolanaFailoverConfig {
+                max_failures: Some(2),
+                ..Default::default()
+            },
+        );
+        stubs[0].set(PROVIDER_ERROR);
+
+        get_slot(&providers).await.unwrap();
+        assert!(providers.health()[0].healthy);
+        assert_eq!(urls(&providers)[0], "http://provider-0");
+
+        get_slot(&providers).await.unwrap();
+        assert!(!providers.health()[0].healthy);
+        assert_eq!(urls(&providers)[0], "http://provider-1");
+
+        // The unhealthy provider is not tried while the healthy one answers
+        get_slot(&providers).await.unwrap();
+        assert_eq!(stubs[0].requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn success_restores_health() {
+        let (providers, stubs) = stub_providers(
+            &[0],
+            SolanaFailoverConfig {
+                max_failures: Some(1),
+                ..Default::default()
+            },
+        );
+        stubs[0].set(PROVIDER_ERROR);
+        assert!(get_slot(&providers).await.is_err());
+        assert!(!providers.health()[0].healthy);
+
+        stubs[0].set(7);
+        assert_eq!(get_slot(&providers).await.unwrap(), 7);
+        let health = &providers.health()[0];
+        assert!(health.healthy);
+        assert_eq!(health.consecutive_failures, 0);
+        assert_eq!(health.requests, 2);
+    }
+
+    #[test]
+    fn latency_is_averaged() {
+        let (providers, _) = stub_providers(&[0], SolanaFailoverConfig::default());
+        let provider = &providers.providers[0];
+
+        provider.record_latency(Duration::from_micros(800));
+        assert_eq!(provider.latency_us.load(Ordering::Relaxed), 800);
+        provider.record_latency(Duration::from_micros(1_600));
+        assert_eq!(provider.latency_us.load(Ordering::Relaxed), 900);
+        provider.record_latency(Duration::from_micros(100));
+        assert_eq!(provider.latency_us.load(Ordering::Relaxed), 800);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn faster_providers_go_first() {
+        let (providers, stubs) = stub_providers(&[50, 10], SolanaFailoverConfig::default());
+        assert_eq!(urls(&providers)[0], "http://provider-0");
+
+        for (provider, stub) in providers.providers.iter().zip(&stubs) {
+            provider
+                .send(RpcRequest::GetHealth, serde_json::Value::Null, 3)
+                .await
+                .unwrap();
+            assert_eq!(stub.requests(), 1);
+        }
+
+        assert_eq!(urls(&providers)[0], "http://provider-1");
+        assert_eq!(get_slot(&providers).await.unwrap(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_reads_are_hedged() {
+        let config = SolanaFailoverConfig {
+            hedge_delay_ms: Some(20),
+            ..Default::default()
+        };
+
+        let (providers, stubs) = stub_providers(&[100, 10], config.clone());
+        assert_eq!(get_slot(&providers).await.unwrap(), 1);
+        assert_eq!(stubs[1].requests(), 1);
+
+        // Fast primary answers before the hedge delay
+        let (providers, stubs) = stub_providers(&[10, 10], config.clone());
+        assert_eq!(get_slot(&providers).await.unwrap(), 0);
+        assert_eq!(stubs[1].requests(), 0);
+
+        // Transactions are never sent twice
+        let (providers, stubs) = stub_providers(&[100, 10], config);
+        let result = providers
+            .send(RpcRequest::SendTransaction, serde_json::Value::Null)
+            .await;
+        assert_eq!(result.unwrap(), 0);
+        assert_eq!(stubs[1].requests(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_hedged_read_waits_for_the_other_provider() {
+        let (providers, stubs) = stub_providers(
+            &[100, 30],
+            SolanaFailoverConfig {
+                hedge_delay_ms: Some(20),
+                ..Default::default()
+            },
+        );
+        stubs[1].set(PROVIDER_ERROR);
+
+        assert_eq!(get_slot(&providers).await.unwrap(), 0);
+        assert_eq!(stubs[1].requests(), 1);
+    }
+}
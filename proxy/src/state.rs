@@ -0,0 +1,203 @@
+use crate::api::{ApiError, ApiResult};
+use crate::chain::Chain;
+use ethers::types::{BlockId, BlockNumber};
+use rome_sdk::rome_evm_client::RomeEVMClient;
+use solana_sdk::commitment_config::CommitmentLevel;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Rome-EVM clients reading Solana state with the commitment requested by the block tag
+pub struct StateClients<C = RomeEVMClient> {
+    latest: Arc<C>,
+    latest_commitment: CommitmentLevel,
+    by_commitment: HashMap<CommitmentLevel, Arc<C>>,
+}
+
+impl<C: Chain> StateClients<C> {
+    pub fn new(
+        latest: Arc<C>,
+        latest_commitment: CommitmentLevel,
+        by_commitment: HashMap<CommitmentLevel, Arc<C>>,
+    ) -> Self {
+        Self {
+            latest,
+            latest_commitment,
+            by_commitment,
+        }
+    }
+
+    /// Select the client serving state at the requested block.
+    /// Only the current state is available, historical blocks are rejected.
+    pub async fn resolve(&self, block: Option<BlockId>) -> ApiResult<&C> {
+        let commitment = match block.unwrap_or(BlockId::Number(BlockNumber::Latest)) {
+            BlockId::Number(BlockNumber::Latest) => return Ok(&self.latest),
+            BlockId::Number(BlockNumber::Pending) => CommitmentLevel::Processed,
+            BlockId::Number(BlockNumber::Safe) => CommitmentLevel::Confirmed,
+            BlockId::Number(BlockNumber::Finalized) => CommitmentLevel::Finalized,
+            BlockId::Number(BlockNumber::Earliest) => return self.at_number(0).await,
+            BlockId::Number(BlockNumber::Number(number)) => {
+                return self.at_number(number.as_u64()).await
+            }
+            BlockId::Hash(hash) => {
+                let block = self
+                    .latest
+                    .block(BlockId::Hash(hash))
+                    .await?
+                    .ok_or(ApiError::BlockNotFound)?;
+                let number = block.number.ok_or(ApiError::BlockNotFound)?;
+                return self.at_number(number.as_u64()).await;
+            }
+        };
+
+        Ok(self.with_commitment(commitment))
+    }
+
+    fn with_commitment(&self, commitment: CommitmentLevel) -> &C {
+        if commitment == self.latest_commitment {
+            return &self.latest;
+        }
+
+        self.by_commitment.get(&commitment).unwrap_or(&self.latest)
+    }
+
+    async fn at_number(&self, number: u64) -> ApiResult<&C> {
+        let latest = self.latest.latest_block_number().await?;
+
+        if number == latest {
+            Ok(&self.latest)
+        } else if number > latest {
+            Err(ApiError::BlockNotFound)
+        } else {
+            Err(ApiError::HistoricalStateUnavailable(number))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::mock::MockChain;
+    use ethers::types::H256;
+
+    fn chain(blocks: usize) -> Arc<MockChain> {
+        let mut chain = MockChain::default();
+        for _ in 0..blocks {
+            chain.push_block(vec![]);
+        }
+
+        Arc::new(chain)
+    }
+
+    /// Latest state is read with the confirmed commitment, a client per other commitment
+    fn state_clients() -> StateClients<MockChain> {
+        StateClients::new(
+            chain(5),
+            CommitmentLevel::Confirmed,
+            HashMap::from([
+                (CommitmentLevel::Processed, chain(5)),
+                (CommitmentLevel::Finalized, chain(5)),
+            ]),
+        )
+    }
+
+    async fn resolve(
+        state: &StateClients<MockChain>,
+        block: impl Into<BlockId>,
+    ) -> ApiResult<*const MockChain> {
+        state
+            .resolve(Some(block.into()))
+            .await
+            .map(|client| client as *const _)
+    }
+
+    #[tokio::test]
+    async fn tags_select_commitment() {
+        let state = state_clients();
+        let latest = Arc::as_ptr(&state.latest);
+        let processed = Arc::as_ptr(&state.by_commitment[&CommitmentLevel::Processed]);
+        let finalized = Arc::as_ptr(&state.by_commitment[&CommitmentLevel::Finalized]);
+
+        assert_eq!(
+            state
+                .resolve(None)
+                .await
+                .map(|client| client as *const _)
+                .ok(),
+            Some(latest)
+        );
+        assert_eq!(
+            resolve(&state, BlockNumber::Latest).await.ok(),
+            Some(latest)
+        );
+        assert_eq!(
+            resolve(&state, BlockNumber::Pending).await.ok(),
+            Some(processed)
+        );
+        // The latest client reads with the confirmed commitment already
+        assert_eq!(resolve(&state, BlockNumber::Safe).await.ok(), Some(latest));
+        assert_eq!(
+            resolve(&state, BlockNumber::Finalized).await.ok(),
+            Some(finalized)
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_commitment_falls_back_to_latest() {
+        let state = StateClients::new(chain(5), CommitmentLevel::Confirmed, HashMap::new());
+        let latest = Arc::as_ptr(&state.latest);
+
+        assert_eq!(
+            resolve(&state, BlockNumber::Pending).await.ok(),
+            Some(latest)
+        );
+        assert_eq!(
+            resolve(&state, BlockNumber::Finalized).await.ok(),
+            Some(latest)
+        );
+    }
+
+    #[tokio::test]
+    async fn only_latest_block_has_state() {
+        let state = state_clients();
+        let latest = Arc::as_ptr(&state.latest);
+
+        assert_eq!(
+            resolve(&state, BlockNumber::Number(4.into())).await.ok(),
+            Some(latest)
+        );
+        assert!(matches!(
+            resolve(&state, BlockNumber::Number(3.into())).await,
+            Err(ApiError::HistoricalStateUnavailable(3))
+        ));
+        assert!(matches!(
+            resolve(&state, BlockNumber::Earliest).await,
+            Err(ApiError::HistoricalStateUnavailable(0))
+        ));
+        assert!(matches!(
+            resolve(&state, BlockNumber::Number(5.into())).await,
+            Err(ApiError::BlockNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn block_hash_resolves_to_its_number() {
+        let state = state_clients();
+        let latest = Arc::as_ptr(&state.latest);
+        let hashes = state
+            .latest
+            .blocks
+            .iter()
+            .map(|block| block.hash.unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(resolve(&state, hashes[4]).await.ok(), Some(latest));
+        assert!(matches!(
+            resolve(&state, hashes[1]).await,
+            Err(ApiError::HistoricalStateUnavailable(1))
+        ));
+        assert!(matches!(
+            resolve(&state, H256::repeat_byte(0xff)).await,
+            Err(ApiError::BlockNotFound)
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::api::ApiResult;
+use crate::filters::{block_logs, get_block};
+use ethers::types::{Block, BlockId, Log, TxHash};
+use jsonrpsee::core::SubscriptionResult;
+use jsonrpsee::{PendingSubscriptionSink, SubscriptionMessage};
+use rome_sdk::rome_evm_client::RomeEVMClient;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+const DEFAULT_POLL_INTERVAL_MS: u64 = 400;
+const DEFAULT_MAX_SUBSCRIPTIONS_PER_CONNECTION: u32 = 1024;
+const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct SubscriptionConfig {
+    /// Interval of polling the Ethereum block storage for new blocks
+    pub poll_interval_ms: Option<u64>,
+    /// Maximum number of active subscriptions per WebSocket connection
+    pub max_subscriptions_per_connection: Option<u32>,
+    /// Number of notifications buffered for each subscriber
+    pub channel_capacity: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SubscriptionKind {
+    NewHeads,
+    Logs,
+    NewPendingTransactions,
+}
+
+/// Broadcasts new blocks, logs and pending transactions to the eth_subscribe subscribers
+pub struct Subscriptions {
+    heads: broadcast::Sender<Arc<Block<TxHash>>>,
+    logs: broadcast::Sender<Arc<Vec<Log>>>,
+    pending_txs: broadcast::Sender<TxHash>,
+    poll_interval: Duration,
+    pub max_subscriptions_per_connection: u32,
+}
+
+impl Subscriptions {
+    pub fn new(config: &SubscriptionConfig) -> Self {
+        let capacity = config.channel_capacity.unwrap_or(DEFAULT_CHANNEL_CAPACITY);
+        Self {
+            heads: broadcast::channel(capacity).0,
+            logs: broadcast::channel(capacity).0,
+            pending_txs: broadcast::channel(capacity).0,
+            poll_interval: Duration::from_millis(
+                config.poll_interval_ms.unwrap_or(DEFAULT_POLL_INTERVAL_MS),
+            ),
+            max_subscriptions_per_connection: config
+                .max_subscriptions_per_connection
+                .unwrap_or(DEFAULT_MAX_SUBSCRIPTIONS_PER_CONNECTION),
+        }
+    }
+
+    pub fn subscribe_heads(&self) -> broadcast::Receiver<Arc<Block<TxHash>>> {
+        self.heads.subscribe()
+    }
+
+    pub fn subscribe_logs(&self) -> broadcast::Receiver<Arc<Vec<Log>>> {
+        self.logs.subscribe()
+    }
+
+    pub fn subscribe_pending_txs(&self) -> broadcast::Receiver<TxHash> {
+        self.pending_txs.subscribe()
+    }
+
+    /// Notify subscribers about the transaction accepted by the proxy
+    pub fn notify_pending_tx(&self, tx_hash: TxHash) {
+        // Fails only if there are no subscribers
+        let _ = self.pending_txs.send(tx_hash);
+    }
+
+    /// Poll the Ethereum block storage and notify subscribers about new blocks
+    pub async fn start(self: Arc<Self>, client: Arc<RomeEVMClient>) {
+        let mut interval = tokio::time::interval(self.poll_interval);
+        let mut next_block = None;
+
+        loop {
+            interval.tick().await;
+            if self.heads.receiver_count() == 0 && self.logs.receiver_count() == 0 {
+                // Nobody is listening - start from the latest block on the next subscription
+                next_block = None;
+                continue;
+            }
+
+            let latest = match client.block_number().await {
+                Ok(latest) => latest.as_u64(),
+                Err(err) => {
+                    tracing::warn!("Subscriptions: failed to get block number: {:?}", err);
+                    continue;
+                }
+            };
+
+            let mut number = next_block.unwrap_or(latest + 1);
+            while number <= latest {
+                match self.notify_block(&client, number).await {
+                    Ok(true) => number += 1,
+                    Ok(false) => break,
+                    Err(err) => {
+                        tracing::warn!("Subscriptions: failed to load block {}: {:?}", number, err);
+                        break;
+                    }
+                }
+            }
+
+            next_block = Some(number);
+        }
+    }
+
+    async fn notify_block(&self, client: &RomeEVMClient, number: u64) -> ApiResult<bool> {
+        let Some(block) = get_block(client, BlockId::Number(number.into())).await? else {
+            return Ok(false);
+        };
+
+        if self.logs.receiver_count() > 0 {
+            let logs = block_logs(client, &block).await?;
+            if !logs.is_empty() {
+                let _ = self.logs.send(Arc::new(logs));
+            }
+        }
+
+        let _ = self.heads.send(Arc::new(block));
+        Ok(true)
+    }
+}
+
+/// Block header as it is sent in the newHeads notification
+pub fn block_header(block: &Block<TxHash>) -> serde_json::Value {
+    let mut header = serde_json::to_value(block).unwrap_or_default();
+    if let Some(header) = header.as_object_mut() {
+        header.remove("transactions");
+    }
+
+    header
+}
+
+/// Accept the subscription and forward items of the broadcast channel to the subscriber
+pub async fn pipe_to_sink<T, V, F>(
+    pending: PendingSubscriptionSink,
+    mut rx: broadcast::Receiver<T>,
+    mut map: F,
+) -> SubscriptionResult
+where
+    T: Clone,
+    V: Serialize,
+    F: FnMut(T) -> Vec<V>,
+{
+    let sink = pending.accept().await?;
+
+    loop {
+        tokio::select! {
+            _ = sink.closed() => return Ok(()),
+            res = rx.recv() => match res {
+                Ok(item) => {
+                    for value in map(item) {
+                        sink.send(SubscriptionMessage::from_json(&value)?).await?;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(
+                        "Subscription {:?} lagged behind, {} notifications skipped",
+                        sink.subscription_id(),
+                        skipped
+                    );
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+}
@@ -0,0 +1,562 @@
+use crate::api::{ApiError, ApiResult};
+use crate::filters::get_block;
+use alloy_rpc_types_trace::geth::{
+    CallFrame, FourByteFrame, GethDebugBuiltInTracerType, GethDebugTracerType,
+    GethDebugTracingOptions, GethTrace, NoopFrame,
+};
+use ethers::types::{Address, BlockId, Bytes, NameOrAddress, TransactionRequest, H256, U256};
+use revm::db::WrapDatabaseRef;
+use revm::primitives::{
+    self as rp, AccountInfo, BlockEnv, Bytecode, CfgEnv, EVMError, Env, ResultAndState, SpecId,
+    TxEnv, TxKind,
+};
+use revm::{inspector_handle_register, DatabaseRef, Evm, Inspector};
+use revm_inspectors::tracing::{
+    FourByteInspector, MuxInspector, TracingInspector, TracingInspectorConfig,
+};
+use rome_sdk::rome_evm_client::RomeEVMClient;
+
+/// Gas limit of the replayed call if the call does not set it
+const DEFAULT_GAS_LIMIT: u64 = 50_000_000;
+/// Replay follows the opcodes and the gas schedule of this hardfork
+const REPLAY_SPEC: SpecId = SpecId::CANCUN;
+
+/// Accounts of the Rome-EVM state a call is replayed on
+pub trait StateReader {
+    fn chain_id(&self) -> u64;
+    fn balance(&self, address: Address) -> ApiResult<U256>;
+    fn nonce(&self, address: Address) -> ApiResult<u64>;
+    fn code(&self, address: Address) -> ApiResult<Bytes>;
+    fn storage(&self, address: Address, slot: U256) -> ApiResult<U256>;
+    fn block_hash(&self, number: u64) -> ApiResult<H256>;
+}
+
+impl StateReader for RomeEVMClient {
+    fn chain_id(&self) -> u64 {
+        RomeEVMClient::chain_id(self)
+    }
+
+    fn balance(&self, address: Address) -> ApiResult<U256> {
+        Ok(self.get_balance(address)?)
+    }
+
+    fn nonce(&self, address: Address) -> ApiResult<u64> {
+        Ok(self.transaction_count(address)?.as_u64())
+    }
+
+    fn code(&self, address: Address) -> ApiResult<Bytes> {
+        Ok(self.get_code(address)?)
+    }
+
+    fn storage(&self, address: Address, slot: U256) -> ApiResult<U256> {
+        Ok(self.eth_get_storage_at(address, slot)?)
+    }
+
+    /// The block storage is async, the replay runs on a worker thread of the multi-threaded runtime
+    fn block_hash(&self, number: u64) -> ApiResult<H256> {
+        let block = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(get_block(self, BlockId::Number(number.into())))
+        })?;
+
+        Ok(block.and_then(|block| block.hash).unwrap_or_default())
+    }
+}
+
+/// Block the call is replayed in
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReplayBlock {
+    pub number: u64,
+    pub timestamp: u64,
+}
+
+/// Replay the call in the EVM on top of the Rome-EVM state and build the output of the tracer.
+/// The EVM follows the Ethereum gas schedule, gas figures may differ from the Rome-EVM ones.
+pub fn trace_call(
+    state: &impl StateReader,
+    block: ReplayBlock,
+    call: &TransactionRequest,
+    options: GethDebugTracingOptions,
+) -> ApiResult<GethTrace> {
+    let db = StateDb(state);
+    let env = replay_env(state.chain_id(), block, call);
+
+    let tracer = match options.tracer {
+        None => {
+            let config = options.config;
+            let (result, inspector) = replay(
+                &db,
+                env,
+                TracingInspector::new(TracingInspectorConfig::from_geth_config(&config)),
+            )?;
+            let output = result.result.output().cloned().unwrap_or_default();
+            return Ok(inspector
+                .into_geth_builder()
+                .geth_traces(result.result.gas_used(), output, config)
+                .into());
+        }
+        Some(GethDebugTracerType::BuiltInTracer(tracer)) => tracer,
+        Some(GethDebugTracerType::JsTracer(_)) => {
+            return Err(ApiError::Unsupported(
+                "JavaScript tracers are not supported".to_string(),
+            ))
+        }
+    };
+
+    let tracer_config = options.tracer_config;
+    let trace = match tracer {
+        GethDebugBuiltInTracerType::CallTracer => {
+            let config = tracer_config.into_call_config().map_err(invalid_config)?;
+            let (result, inspector) = replay(
+                &db,
+                env,
+                TracingInspector::new(TracingInspectorConfig::from_geth_call_config(&config)),
+            )?;
+            let mut frame = inspector
+                .into_geth_builder()
+                .geth_call_traces(config, result.result.gas_used());
+            geth_call_errors(&mut frame);
+            frame.into()
+        }
+        GethDebugBuiltInTracerType::PreStateTracer => {
+            let config = tracer_config
+                .into_pre_state_config()
+                .map_err(invalid_config)?;
+            let (result, inspector) = replay(
+                &db,
+                env,
+                TracingInspector::new(TracingInspectorConfig::from_geth_prestate_config(&config)),
+            )?;
+            inspector
+                .into_geth_builder()
+                .geth_prestate_traces(&result, config, &db)?
+                .into()
+        }
+        GethDebugBuiltInTracerType::FourByteTracer => {
+            let (_, inspector) = replay(&db, env, FourByteInspector::default())?;
+            FourByteFrame::from(inspector).into()
+        }
+        GethDebugBuiltInTracerType::MuxTracer => {
+            let config = tracer_config.into_mux_config().map_err(invalid_config)?;
+            let inspector = MuxInspector::try_from_config(config)
+                .map_err(|err| ApiError::Unsupported(err.to_string()))?;
+            let (result, inspector) = replay(&db, env, inspector)?;
+            GethTrace::MuxTracer(inspector.try_into_mux_frame(&result, &db)?)
+        }
+        GethDebugBuiltInTracerType::NoopTracer => NoopFrame::default().into(),
+    };
+
+    Ok(trace)
+}
+
+fn replay<'a, S, I>(
+    db: &'a StateDb<'a, S>,
+    env: Env,
+    inspector: I,
+) -> ApiResult<(ResultAndState, I)>
+where
+    S: StateReader,
+    I: Inspector<WrapDatabaseRef<&'a StateDb<'a, S>>>,
+{
+    let mut evm = Evm::builder()
+        .with_ref_db(db)
+        .with_external_context(inspector)
+        .with_env(Box::new(env))
+        .with_spec_id(REPLAY_SPEC)
+        .append_handler_register(inspector_handle_register)
+        .build();
+
+    let result = evm.transact().map_err(|err| match err {
+        EVMError::Database(err) => err,
+        err => ApiError::InvalidTransaction(err.to_string()),
+    })?;
+
+    Ok((result, evm.into_context().external))
+}
+
+/// The replay checks the sender balance but not the nonce, the base fee and the block gas limit
+fn replay_env(chain_id: u64, block: ReplayBlock, call: &TransactionRequest) -> Env {
+    let mut cfg = CfgEnv::default();
+    cfg.chain_id = chain_id;
+    cfg.disable_base_fee = true;
+    cfg.disable_block_gas_limit = true;
+
+    let block = BlockEnv {
+        number: rp::U256::from(block.number),
+        timestamp: rp::U256::from(block.timestamp),
+        gas_limit: rp::U256::from(DEFAULT_GAS_LIMIT),
+        ..Default::default()
+    };
+
+    let transact_to = match &call.to {
+        Some(NameOrAddress::Address(to)) => TxKind::Call(to_address(*to)),
+        _ => TxKind::Create,
+    };
+    let tx = TxEnv {
+        caller: to_address(call.from.unwrap_or_default()),
+        gas_limit: call.gas.map_or(DEFAULT_GAS_LIMIT, |gas| gas.as_u64()),
+        gas_price: to_u256(call.gas_price.unwrap_or_default()),
+        transact_to,
+        value: to_u256(call.value.unwrap_or_default()),
+        data: call
+            .data
+            .as_ref()
+            .map(|data| data.to_vec())
+            .unwrap_or_default()
+            .into(),
+        nonce: None,
+        chain_id: Some(chain_id),
+        ..Default::default()
+    };
+
+    Env { cfg, block, tx }
+}
+
+/// Rome-EVM state read by the EVM
+struct StateDb<'a, S>(&'a S);
+
+impl<S: StateReader> DatabaseRef for StateDb<'_, S> {
+    type Error = ApiError;
+
+    fn basic_ref(&self, address: rp::Address) -> ApiResult<Option<AccountInfo>> {
+        let address = Address::from(address.into_array());
+        let balance = self.0.balance(address)?;
+        let nonce = self.0.nonce(address)?;
+        let code = self.0.code(address)?;
+        if balance.is_zero() && nonce == 0 && code.is_empty() {
+            return Ok(None);
+        }
+
+        let code = Bytecode::new_raw(code.to_vec().into());
+        Ok(Some(AccountInfo::new(
+            to_u256(balance),
+            nonce,
+            code.hash_slow(),
+            code,
+        )))
+    }
+
+    /// Code is loaded together with the account
+    fn code_by_hash_ref(&self, _code_hash: rp::B256) -> ApiResult<Bytecode> {
+        Ok(Bytecode::default())
+    }
+
+    fn storage_ref(&self, address: rp::Address, index: rp::U256) -> ApiResult<rp::U256> {
+        let value = self.0.storage(
+            Address::from(address.into_array()),
+            U256(index.into_limbs()),
+        )?;
+        Ok(to_u256(value))
+    }
+
+    fn block_hash_ref(&self, number: rp::U256) -> ApiResult<rp::B256> {
+        let hash = self.0.block_hash(number.saturating_to())?;
+        Ok(rp::B256::from(hash.0))
+    }
+}
+
+/// Call frames are built with the parity error messages, callTracer of geth reports its own ones
+fn geth_call_errors(frame: &mut CallFrame) {
+    if let Some(error) = &mut frame.error {
+        let geth_error = match error.as_str() {
+            "Reverted" => "execution reverted",
+            "Out of gas" => "out of gas",
+            "Bad instruction" => "invalid opcode",
+            "Bad jump destination" => "invalid jump destination",
+            "Built-in failed" => "precompiled failed",
+            _ => error.as_str(),
+        };
+        *error = geth_error.to_string();
+    }
+
+    frame.calls.iter_mut().for_each(geth_call_errors);
+}
+
+fn to_address(address: Address) -> rp::Address {
+    rp::Address::from(address.0)
+}
+
+fn to_u256(value: U256) -> rp::U256 {
+    rp::U256::from_limbs(value.0)
+}
+
+fn invalid_config(err: serde_json::Error) -> ApiError {
+    ApiError::Unsupported(format!("invalid tracer config: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    const CALLER: u64 = 0xca11;
+    const OUTER: u64 = 0xaa;
+    const INNER: u64 = 0xbb;
+    const STORE: u64 = 0xcc;
+    const REVERT: u64 = 0xdd;
+
+    #[derive(Default)]
+    struct Account {
+        balance: U256,
+        nonce: u64,
+        code: Vec<u8>,
+        storage: HashMap<U256, U256>,
+    }
+
+    /// Rome-EVM state of the tests
+    #[derive(Default)]
+    struct MockState(HashMap<Address, Account>);
+
+    impl StateReader for MockState {
+        fn chain_id(&self) -> u64 {
+            1001
+        }
+
+        fn balance(&self, address: Address) -> ApiResult<U256> {
+            Ok(self.0.get(&address).map_or(U256::zero(), |acc| acc.balance))
+        }
+
+        fn nonce(&self, address: Address) -> ApiResult<u64> {
+            Ok(self.0.get(&address).map_or(0, |acc| acc.nonce))
+        }
+
+        fn code(&self, address: Address) -> ApiResult<Bytes> {
+            Ok(self
+                .0
+                .get(&address)
+                .map_or(vec![], |acc| acc.code.clone())
+                .into())
+        }
+
+        fn storage(&self, address: Address, slot: U256) -> ApiResult<U256> {
+            Ok(self
+                .0
+                .get(&address)
+                .and_then(|acc| acc.storage.get(&slot).copied())
+                .unwrap_or_default())
+        }
+
+        fn block_hash(&self, _number: u64) -> ApiResult<H256> {
+            Ok(H256::zero())
+        }
+    }
+
+    fn address(n: u64) -> Address {
+        Address::from_low_u64_be(n)
+    }
+
+    /// OUTER calls INNER, INNER sets the slot 1 to 0x2a and emits an empty log,
+    /// STORE sets the empty slot 0 to 0x2a, REVERT reverts
+    fn state() -> MockState {
+        let mut outer = vec![
+            0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73,
+        ];
+        outer.extend_from_slice(address(INNER).as_bytes());
+        outer.extend_from_slice(&[0x5a, 0xf1, 0x50, 0x00]);
+
+        MockState(HashMap::from([
+            (
+                address(CALLER),
+                Account {
+                    balance: U256::exp10(18),
+                    nonce: 5,
+                    ..Default::default()
+                },
+            ),
+            (
+                address(OUTER),
+                Account {
+                    code: outer,
+                    ..Default::default()
+                },
+            ),
+            (
+                address(INNER),
+                Account {
+                    code: vec![
+                        0x60, 0x2a, 0x60, 0x01, 0x55, 0x60, 0x00, 0x60, 0x00, 0xa0, 0x00,
+                    ],
+                    storage: HashMap::from([(U256::one(), U256::from(7))]),
+                    ..Default::default()
+                },
+            ),
+            (
+                address(STORE),
+                Account {
+                    code: vec![0x60, 0x2a, 0x60, 0x00, 0x55, 0x00],
+                    ..Default::default()
+                },
+            ),
+            (
+                address(REVERT),
+                Account {
+                    code: vec![0x60, 0x00, 0x60, 0x00, 0xfd],
+                    ..Default::default()
+                },
+            ),
+        ]))
+    }
+
+    fn call(to: u64) -> TransactionRequest {
+        TransactionRequest::new()
+            .from(address(CALLER))
+            .to(address(to))
+            .gas(100_000)
+    }
+
+    fn trace(call: &TransactionRequest, options: serde_json::Value) -> serde_json::Value {
+        let options = serde_json::from_value(options).expect("valid options");
+        let trace = trace_call(&state(), ReplayBlock::default(), call, options).expect("trace");
+        serde_json::to_value(trace).expect("serializable trace")
+    }
+
+    #[test]
+    fn struct_logs_of_a_storage_write() {
+        // 21000 intrinsic, 3 + 3 pushes, 2100 cold slot + 20000 zero to non-zero write
+        assert_eq!(
+            trace(&call(STORE), json!({})),
+            json!({
+                "failed": false,
+                "gas": 43106,
+                "returnValue": "",
+                "structLogs": [
+                    {"pc": 0, "op": "PUSH1", "gas": 79000, "gasCost": 3, "depth": 1, "stack": []},
+                    {"pc": 2, "op": "PUSH1", "gas": 78997, "gasCost": 3, "depth": 1, "stack": ["0x2a"]},
+                    {
+                        "pc": 4, "op": "SSTORE", "gas": 78994, "gasCost": 22100, "depth": 1,
+                        "stack": ["0x2a", "0x0"],
+                        "storage": {
+                            "0000000000000000000000000000000000000000000000000000000000000000":
+                                "000000000000000000000000000000000000000000000000000000000000002a"
+                        }
+                    },
+                    {"pc": 5, "op": "STOP", "gas": 56894, "gasCost": 0, "depth": 1, "stack": []}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn struct_logs_follow_nested_calls() {
+        let trace = trace(&call(OUTER), json!({"disableStack": true}));
+        let depths = trace["structLogs"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|log| {
+                (
+                    log["op"].as_str().unwrap().to_string(),
+                    log["depth"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let call = depths.iter().position(|(op, _)| op == "CALL").unwrap();
+        assert_eq!(depths[call].1, 1);
+        assert_eq!(depths[call + 1], ("PUSH1".to_string(), 2));
+        assert!(depths.contains(&("LOG0".to_string(), 2)));
+        assert_eq!(depths.last().unwrap(), &("STOP".to_string(), 1));
+        assert!(trace["structLogs"][0].get("stack").is_none());
+    }
+
+    #[test]
+    fn call_tracer_reports_nested_frames_and_logs() {
+        let trace = trace(
+            &call(OUTER),
+            json!({"tracer": "callTracer", "tracerConfig": {"withLog": true}}),
+        );
+
+        assert_eq!(trace["type"], "CALL");
+        assert_eq!(trace["from"], json!(address(CALLER)));
+        assert_eq!(trace["to"], json!(address(OUTER)));
+        assert!(trace.get("logs").is_none());
+
+        let calls = trace["calls"].as_array().unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0]["type"], "CALL");
+        assert_eq!(calls[0]["from"], json!(address(OUTER)));
+        assert_eq!(calls[0]["to"], json!(address(INNER)));
+        assert_eq!(calls[0]["logs"][0]["address"], json!(address(INNER)));
+        assert!(calls[0].get("calls").is_none());
+    }
+
+    #[test]
+    fn call_tracer_only_top_call() {
+        let trace = trace(
+            &call(OUTER),
+            json!({"tracer": "callTracer", "tracerConfig": {"onlyTopCall": true}}),
+        );
+        assert!(trace.get("calls").is_none());
+    }
+
+    #[test]
+    fn call_tracer_reports_reverts() {
+        let trace = trace(&call(REVERT), json!({"tracer": "callTracer"}));
+        assert_eq!(trace["error"], "execution reverted");
+        assert!(trace.get("output").is_none());
+    }
+
+    #[test]
+    fn prestate_tracer_reports_touched_storage() {
+        let trace = trace(&call(OUTER), json!({"tracer": "prestateTracer"}));
+
+        assert_eq!(trace[format!("{:?}", address(CALLER))]["nonce"], 5);
+        assert_eq!(
+            trace[format!("{:?}", address(INNER))]["storage"],
+            json!({
+                "0x0000000000000000000000000000000000000000000000000000000000000001":
+                    "0x0000000000000000000000000000000000000000000000000000000000000007"
+            })
+        );
+        assert!(trace[format!("{:?}", address(OUTER))]["code"].is_string());
+    }
+
+    #[test]
+    fn prestate_tracer_diff_mode() {
+        let trace = trace(
+            &call(OUTER),
+            json!({"tracer": "prestateTracer", "tracerConfig": {"diffMode": true}}),
+        );
+
+        let inner = format!("{:?}", address(INNER));
+        let slot = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        assert_eq!(
+            trace["pre"][&inner]["storage"][slot],
+            "0x0000000000000000000000000000000000000000000000000000000000000007"
+        );
+        assert_eq!(
+            trace["post"][&inner]["storage"][slot],
+            "0x000000000000000000000000000000000000000000000000000000000000002a"
+        );
+        assert_eq!(trace["post"][format!("{:?}", address(CALLER))]["nonce"], 6);
+    }
+
+    #[test]
+    fn four_byte_tracer_counts_selectors() {
+        let call = call(STORE).data(vec![0x12, 0x34, 0x56, 0x78, 0xaa, 0xbb]);
+        assert_eq!(
+            trace(&call, json!({"tracer": "4byteTracer"})),
+            json!({"0x12345678-2": 1})
+        );
+    }
+
+    #[test]
+    fn invalid_transactions_are_rejected() {
+        let call = call(STORE).value(U256::exp10(19));
+        let options = serde_json::from_value(json!({"tracer": "callTracer"})).unwrap();
+        assert!(matches!(
+            trace_call(&state(), ReplayBlock::default(), &call, options),
+            Err(ApiError::InvalidTransaction(_))
+        ));
+    }
+
+    #[test]
+    fn js_tracers_are_unsupported() {
+        let options = serde_json::from_value(json!({"tracer": "{result: function() {}}"})).unwrap();
+        assert!(matches!(
+            trace_call(&state(), ReplayBlock::default(), &call(STORE), options),
+            Err(ApiError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,3 @@
+// gets receipts of block 2 by hash
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getBlockReceipts","params":["0x000000000000000000000000000000000000000000000000000000000000b002"]}
+<< {"jsonrpc":"2.0","id":1,"result":[{"transactionHash":"0x0000000000000000000000000000000000000000000000000000000000007020","transactionIndex":"0x0","blockHash":"0x000000000000000000000000000000000000000000000000000000000000b002","blockNumber":"0x2","from":"0x00000000000000000000000000000000000000dd","to":null,"cumulativeGasUsed":"0x5208","gasUsed":"0x5208","contractAddress":"0x00000000000000000000000000000000000000ee","logs":[],"status":"0x1","logsBloom":"0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","type":"0x2","effectiveGasPrice":"0x3b9aca00"}]}
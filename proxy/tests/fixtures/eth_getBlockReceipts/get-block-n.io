@@ -0,0 +1,3 @@
+// gets receipts of block 1
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getBlockReceipts","params":["0x1"]}
+<< {"jsonrpc":"2.0","id":1,"result":[{"transactionHash":"0x0000000000000000000000000000000000000000000000000000000000007010","transactionIndex":"0x0","blockHash":"0x000000000000000000000000000000000000000000000000000000000000b001","blockNumber":"0x1","from":"0x00000000000000000000000000000000000000aa","to":"0x00000000000000000000000000000000000000bb","cumulativeGasUsed":"0x5208","gasUsed":"0x5208","contractAddress":null,"logs":[],"status":"0x1","logsBloom":"0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","type":"0x0","effectiveGasPrice":"0x3b9aca00"},{"transactionHash":"0x0000000000000000000000000000000000000000000000000000000000007011","transactionIndex":"0x1","blockHash":"0x000000000000000000000000000000000000000000000000000000000000b001","blockNumber":"0x1","from":"0x00000000000000000000000000000000000000aa","to":"0x00000000000000000000000000000000000000cc","cumulativeGasUsed":"0xa410","gasUsed":"0x5208","contractAddress":null,"logs":[{"address":"0x00000000000000000000000000000000000000cc","topics":["0x00000000000000000000000000000000000000000000000000000000000000e0"],"data":"0x01","blockHash":"0x000000000000000000000000000000000000000000000000000000000000b001","blockNumber":"0x1","transactionHash":"0x0000000000000000000000000000000000000000000000000000000000007011","transactionIndex":"0x1","logIndex":"0x0","removed":false}],"status":"0x1","logsBloom":"0x00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000","type":"0x2","effectiveGasPrice":"0x3b9aca00"}]}
@@ -0,0 +1,3 @@
+// gets receipts of the genesis block
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getBlockReceipts","params":["earliest"]}
+<< {"jsonrpc":"2.0","id":1,"result":[]}
@@ -0,0 +1,3 @@
+// gets receipts of a block not yet produced
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getBlockReceipts","params":["0x9"]}
+<< {"jsonrpc":"2.0","id":1,"result":null}
@@ -0,0 +1,3 @@
+// gets the transaction count of block 1 by hash
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getBlockTransactionCountByHash","params":["0x000000000000000000000000000000000000000000000000000000000000b001"]}
+<< {"jsonrpc":"2.0","id":1,"result":"0x2"}
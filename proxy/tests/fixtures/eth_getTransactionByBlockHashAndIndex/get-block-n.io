@@ -0,0 +1,3 @@
+// gets the first transaction of block 1 by hash
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getTransactionByBlockHashAndIndex","params":["0x000000000000000000000000000000000000000000000000000000000000b001","0x0"]}
+<< {"jsonrpc":"2.0","id":1,"result":{"hash":"0x0000000000000000000000000000000000000000000000000000000000007010","nonce":"0x0","blockHash":"0x000000000000000000000000000000000000000000000000000000000000b001","blockNumber":"0x1","transactionIndex":"0x0","from":"0x00000000000000000000000000000000000000aa","to":"0x00000000000000000000000000000000000000bb","value":"0x3e8","gasPrice":"0x3b9aca00","gas":"0x5208","input":"0x","v":"0x7f5","r":"0x1234","s":"0x5678","type":"0x0","chainId":"0x3e9"}}
@@ -0,0 +1,3 @@
+// gets a transaction of an unknown block hash
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getTransactionByBlockHashAndIndex","params":["0xffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff","0x0"]}
+<< {"jsonrpc":"2.0","id":1,"result":null}
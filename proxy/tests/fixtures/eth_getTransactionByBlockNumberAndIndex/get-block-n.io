@@ -0,0 +1,3 @@
+// gets the second transaction of block 1
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getTransactionByBlockNumberAndIndex","params":["0x1","0x1"]}
+<< {"jsonrpc":"2.0","id":1,"result":{"hash":"0x0000000000000000000000000000000000000000000000000000000000007011","nonce":"0x1","blockHash":"0x000000000000000000000000000000000000000000000000000000000000b001","blockNumber":"0x1","transactionIndex":"0x1","from":"0x00000000000000000000000000000000000000aa","to":"0x00000000000000000000000000000000000000cc","value":"0x7d0","gasPrice":"0x3b9aca00","gas":"0x5208","input":"0x","v":"0x1","r":"0x1235","s":"0x5679","type":"0x2","accessList":[],"maxPriorityFeePerGas":"0x3b9aca00","maxFeePerGas":"0x77359400","chainId":"0x3e9"}}
@@ -0,0 +1,3 @@
+// gets the first transaction of the latest block
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getTransactionByBlockNumberAndIndex","params":["latest","0x0"]}
+<< {"jsonrpc":"2.0","id":1,"result":{"hash":"0x0000000000000000000000000000000000000000000000000000000000007020","nonce":"0x0","blockHash":"0x000000000000000000000000000000000000000000000000000000000000b002","blockNumber":"0x2","transactionIndex":"0x0","from":"0x00000000000000000000000000000000000000dd","to":null,"value":"0x3e8","gasPrice":"0x3b9aca00","gas":"0x186a0","input":"0x6080","v":"0x1","r":"0x1234","s":"0x5678","type":"0x2","accessList":[],"maxPriorityFeePerGas":"0x3b9aca00","maxFeePerGas":"0x77359400","chainId":"0x3e9"}}
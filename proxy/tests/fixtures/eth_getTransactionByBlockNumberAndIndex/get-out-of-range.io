@@ -0,0 +1,3 @@
+// gets a transaction index past the end of the block
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getTransactionByBlockNumberAndIndex","params":["0x1","0x5"]}
+<< {"jsonrpc":"2.0","id":1,"result":null}
@@ -0,0 +1,3 @@
+// gets the uncle count of a block not yet produced
+>> {"jsonrpc":"2.0","id":1,"method":"eth_getUncleCountByBlockNumber","params":["0x9"]}
+<< {"jsonrpc":"2.0","id":1,"result":null}
@@ -0,0 +1,200 @@
+//! Expiry of mempool transactions by the single `DelayQueue` scheduler of the mempool
+//! split into independently locked shards.
+//!
+//! Transactions are added to the real mempool and forwarded by its sender queues to a stub
+//! sender, which accepts every transaction at once. Transactions are added and expired
+//! while other tasks route events to the sender queues, like the admin API does.
+//!
+//! cargo bench -p rhea --bench mempool_expiry
+
+use async_trait::async_trait;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ethers::types::{Address, Transaction};
+use ethers::utils::to_checksum;
+use rhea::mempool::{Mempool, MempoolConfig};
+use rhea::mempool_sender::{OverflowPolicy, QueuedTx, RetryPolicy, SenderConfig};
+use rhea::policy::{Policy, PolicyConfig};
+use rhea::rome_sender::{SendError, TxSender};
+use rhea::store::{MempoolStore, StoreRetention};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const CHAIN_ID: u64 = 1001;
+const TXS: u64 = 5_000;
+const SENDERS: u64 = 1_000;
+const ROUTING_TASKS: usize = 8;
+const ROUTES_PER_TASK: usize = 2_000;
+const MEMPOOL_TTL: Duration = Duration::from_millis(5);
+const SHARDS: [usize; 4] = [1, 4, 16, 64];
+
+/// Accepts every transaction, the next nonce of the sender follows the last accepted one
+#[derive(Default)]
+struct StubSender {
+    nonces: Mutex<HashMap<Address, u64>>,
+}
+
+#[async_trait]
+impl TxSender for StubSender {
+    fn transaction_count(&self, _chain_id: u64, address: Address) -> anyhow::Result<u64> {
+        Ok(self
+            .nonces
+            .lock()
+            .expect("nonces lock poisoned")
+            .get(&address)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    fn is_shadow(&self) -> bool {
+        false
+    }
+
+    async fn send_transaction(
+        &self,
+        _chain_id: u64,
+        sender_addr: &str,
+        tx: &QueuedTx,
+    ) -> Result<(), SendError> {
+        let address = Address::from_str(sender_addr).map_err(|err| SendError::Send(err.into()))?;
+        let nonce = tx.tx.nonce().map_or(0, |nonce| nonce.as_u64());
+        self.nonces
+            .lock()
+            .expect("nonces lock poisoned")
+            .insert(address, nonce + 1);
+
+        Ok(())
+    }
+}
+
+fn config(shards: usize) -> MempoolConfig {
+    MempoolConfig {
+        mempool_ttl: MEMPOOL_TTL,
+        forward_queued: false,
+        shards,
+        dropped_senders_capacity: 1_024,
+        expiry_capacity: 65_536,
+        sender: SenderConfig {
+            chain_id: CHAIN_ID,
+            sender_ttl: Duration::from_secs(1),
+            gap_timeout: Duration::from_millis(100),
+            fee_bump_percent: 10,
+            batch_size: 100,
+            queue_capacity: 1_024,
+            overflow: OverflowPolicy::Reject,
+            retry: RetryPolicy {
+                max_retries: 0,
+                backoff_base: Duration::ZERO,
+                backoff_cap: Duration::ZERO,
+                jitter_percent: 0,
+            },
+        },
+    }
+}
+
+async fn mempool(shards: usize) -> Arc<Mempool> {
+    let retention = StoreRetention {
+        tx_ttl: 2 * MEMPOOL_TTL,
+        nonce_ttl: Duration::from_secs(3600),
+        dead_letter_ttl: Duration::from_secs(3600),
+    };
+    let (store, stored_state) = MempoolStore::open(CHAIN_ID, None, retention)
+        .await
+        .expect("in-memory store");
+
+    Mempool::new(
+        Arc::new(StubSender::default()),
+        config(shards),
+        Arc::new(store),
+        stored_state,
+        Arc::new(Policy::new(CHAIN_ID, PolicyConfig::default())),
+    )
+    .await
+}
+
+/// Legacy transaction, nonces of every sender follow each other
+fn transaction(index: u64, senders: &[Address]) -> Transaction {
+    let mut tx = Transaction {
+        from: senders[(index % SENDERS) as usize],
+        nonce: (index / SENDERS).into(),
+        to: Some(Address::zero()),
+        gas: 21_000.into(),
+        gas_price: Some(1_000_000_000u64.into()),
+        value: 1.into(),
+        v: (CHAIN_ID * 2 + 35).into(),
+        r: 1.into(),
+        s: 1.into(),
+        chain_id: Some(CHAIN_ID.into()),
+        ..Default::default()
+    };
+    tx.hash = tx.hash();
+    tx
+}
+
+/// Add transactions and route events to sender queues meanwhile
+async fn run(mempool: Arc<Mempool>, senders: Arc<Vec<Address>>, next_tx: Arc<AtomicU64>) {
+    let updates = tokio::spawn({
+        let mempool = mempool.clone();
+        let senders = senders.clone();
+        async move {
+            let first = next_tx.fetch_add(TXS, Ordering::Relaxed);
+            for index in first..first + TXS {
+                mempool.add_tx(&transaction(index, &senders)).await;
+            }
+        }
+    });
+
+    let routing = (0..ROUTING_TASKS)
+        .map(|task| {
+            let mempool = mempool.clone();
+            let senders = senders.clone();
+            tokio::spawn(async move {
+                for route in 0..ROUTES_PER_TASK {
+                    let sender = senders[(task * ROUTES_PER_TASK + route) % senders.len()];
+                    mempool
+                        .pause(Some(&to_checksum(&sender, None)), false)
+                        .await;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for task in routing {
+        task.await.expect("routing task");
+    }
+
+    updates.await.expect("update task");
+}
+
+fn mempool_expiry(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("tokio runtime");
+    let senders = Arc::new(
+        (0..SENDERS)
+            .map(|sender| Address::from_low_u64_be(sender + 1))
+            .collect::<Vec<_>>(),
+    );
+
+    let mut group = c.benchmark_group("mempool_expiry");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements(
+        TXS + (ROUTING_TASKS * ROUTES_PER_TASK) as u64,
+    ));
+
+    for shards in SHARDS {
+        let mempool = runtime.block_on(mempool(shards));
+        let next_tx = Arc::new(AtomicU64::new(0));
+        group.bench_with_input(BenchmarkId::new("delay_queue", shards), &shards, |b, _| {
+            b.to_async(&runtime)
+                .iter(|| run(mempool.clone(), senders.clone(), next_tx.clone()))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, mempool_expiry);
+criterion_main!(benches);
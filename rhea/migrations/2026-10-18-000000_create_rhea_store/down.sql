@@ -0,0 +1,3 @@
+DROP TABLE rhea_dead_letters;
+DROP TABLE rhea_nonces;
+DROP TABLE rhea_txs;
@@ -0,0 +1,30 @@
+CREATE TABLE rhea_txs (
+    chain_id BIGINT NOT NULL,
+    hash TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    nonce BIGINT NOT NULL,
+    status TEXT NOT NULL,
+    received_at BIGINT NOT NULL,
+    PRIMARY KEY (chain_id, hash)
+);
+
+CREATE TABLE rhea_nonces (
+    chain_id BIGINT NOT NULL,
+    sender TEXT NOT NULL,
+    nonce BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    PRIMARY KEY (chain_id, sender)
+);
+
+CREATE TABLE rhea_dead_letters (
+    chain_id BIGINT NOT NULL,
+    hash TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    nonce BIGINT NOT NULL,
+    raw_tx TEXT NOT NULL,
+    error TEXT NOT NULL,
+    attempts BIGINT NOT NULL,
+    first_seen BIGINT NOT NULL,
+    failed_at BIGINT NOT NULL,
+    PRIMARY KEY (chain_id, hash)
+);
@@ -0,0 +1,129 @@
+use crate::api::{AdminServer, ApiError, ApiResult};
+use crate::mempool::Mempool;
+use crate::mempool_sender::SenderInfo;
+use crate::rome_sender::{ClientHealth, RomeSender};
+use crate::store::{DeadLetter, StoredTx};
+use anyhow::Context;
+use async_trait::async_trait;
+use ethers::types::TxHash;
+use jsonrpsee::server::{ServerBuilder, ServerHandle};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Mempool and Solana clients of the rollup
+pub struct RollupState {
+    pub mempool: Arc<Mempool>,
+    pub rome: Arc<RomeSender>,
+}
+
+pub struct RheaAdmin {
+    rollups: HashMap<u64, RollupState>,
+}
+
+impl RheaAdmin {
+    pub fn new(rollups: HashMap<u64, RollupState>) -> Self {
+        Self { rollups }
+    }
+
+    fn rollup(&self, chain_id: u64) -> ApiResult<&RollupState> {
+        self.rollups
+            .get(&chain_id)
+            .ok_or(ApiError::UnknownRollup(chain_id))
+    }
+
+    fn mempool(&self, chain_id: u64) -> ApiResult<&Mempool> {
+        Ok(&self.rollup(chain_id)?.mempool)
+    }
+
+    async fn set_paused(
+        &self,
+        chain_id: u64,
+        sender: Option<String>,
+        paused: bool,
+    ) -> ApiResult<()> {
+        self.mempool(chain_id)?
+            .pause(sender.as_deref(), paused)
+            .await;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AdminServer for Arc<RheaAdmin> {
+    async fn rollups(&self) -> ApiResult<Vec<u64>> {
+        let mut chain_ids = self.rollups.keys().copied().collect::<Vec<_>>();
+        chain_ids.sort();
+        Ok(chain_ids)
+    }
+
+    async fn senders(&self, chain_id: u64) -> ApiResult<HashMap<String, SenderInfo>> {
+        Ok(self.mempool(chain_id)?.senders().await)
+    }
+
+    async fn tx_status(&self, chain_id: u64, hash: TxHash) -> ApiResult<Option<StoredTx>> {
+        Ok(self.mempool(chain_id)?.tx_status(&hash))
+    }
+
+    async fn drop_tx(&self, chain_id: u64, hash: TxHash) -> ApiResult<bool> {
+        Ok(self.mempool(chain_id)?.drop_tx(&hash).await)
+    }
+
+    async fn drop_sender(&self, chain_id: u64, sender: String) -> ApiResult<()> {
+        if self.mempool(chain_id)?.drop_sender(&sender).await {
+            Ok(())
+        } else {
+            Err(ApiError::UnknownSender(sender))
+        }
+    }
+
+    async fn resend_tx(&self, chain_id: u64, hash: TxHash) -> ApiResult<bool> {
+        Ok(self.mempool(chain_id)?.resend_tx(hash).await)
+    }
+
+    async fn pause(&self, chain_id: u64, sender: Option<String>) -> ApiResult<()> {
+        self.set_paused(chain_id, sender, true).await
+    }
+
+    async fn resume(&self, chain_id: u64, sender: Option<String>) -> ApiResult<()> {
+        self.set_paused(chain_id, sender, false).await
+    }
+
+    async fn solana_clients(&self, chain_id: u64) -> ApiResult<Vec<ClientHealth>> {
+        Ok(self.rollup(chain_id)?.rome.health())
+    }
+
+    async fn dead_letters(&self, chain_id: u64) -> ApiResult<HashMap<TxHash, DeadLetter>> {
+        Ok(self.mempool(chain_id)?.dead_letters())
+    }
+
+    async fn dead_letter(&self, chain_id: u64, hash: TxHash) -> ApiResult<Option<DeadLetter>> {
+        Ok(self.mempool(chain_id)?.dead_letter(&hash))
+    }
+
+    async fn requeue_dead_letter(&self, chain_id: u64, hash: TxHash) -> ApiResult<bool> {
+        Ok(self.mempool(chain_id)?.requeue_dead_letter(hash).await?)
+    }
+
+    async fn purge_dead_letters(
+        &self,
+        chain_id: u64,
+        hashes: Option<Vec<TxHash>>,
+    ) -> ApiResult<usize> {
+        Ok(self.mempool(chain_id)?.purge_dead_letters(hashes).await)
+    }
+}
+
+pub async fn start_rpc_server(
+    server: Arc<RheaAdmin>,
+    host: SocketAddr,
+) -> anyhow::Result<ServerHandle> {
+    tracing::info!("Starting the admin RPC server at {host}");
+
+    let rpc = ServerBuilder::default()
+        .build(host)
+        .await
+        .context("Unable to start the admin RPC server")?;
+
+    Ok(rpc.start(AdminServer::into_rpc(server)))
+}
@@ -0,0 +1,104 @@
+pub mod admin;
+
+use crate::mempool_sender::SenderInfo;
+use crate::rome_sender::ClientHealth;
+use crate::store::{DeadLetter, StoredTx};
+use ethers::types::TxHash;
+use jsonrpsee::proc_macros::rpc;
+use jsonrpsee::types::error::INTERNAL_ERROR_CODE;
+use jsonrpsee::types::ErrorObjectOwned;
+use std::collections::HashMap;
+use thiserror::Error;
+
+// EIP-1474 error codes
+const RESOURCE_NOT_FOUND_CODE: i32 = -32001;
+
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("Unknown rollup {0}")]
+    UnknownRollup(u64),
+
+    #[error("Unknown sender {0}")]
+    UnknownSender(String),
+
+    #[error("Rhea error: {0}")]
+    Rhea(String),
+}
+
+impl From<ApiError> for ErrorObjectOwned {
+    fn from(e: ApiError) -> ErrorObjectOwned {
+        match e {
+            ApiError::UnknownRollup(_) | ApiError::UnknownSender(_) => {
+                ErrorObjectOwned::owned(RESOURCE_NOT_FOUND_CODE, e.to_string(), None::<String>)
+            }
+            ApiError::Rhea(_) => {
+                ErrorObjectOwned::owned(INTERNAL_ERROR_CODE, e.to_string(), None::<String>)
+            }
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Rhea(format!("{:#}", value))
+    }
+}
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+#[rpc(server)]
+pub trait Admin {
+    /// Chain ids of the served rollups
+    #[method(name = "rollups")]
+    async fn rollups(&self) -> ApiResult<Vec<u64>>;
+
+    /// Active sender queues
+    #[method(name = "senders")]
+    async fn senders(&self, chain_id: u64) -> ApiResult<HashMap<String, SenderInfo>>;
+
+    #[method(name = "txStatus")]
+    async fn tx_status(&self, chain_id: u64, hash: TxHash) -> ApiResult<Option<StoredTx>>;
+
+    /// Drop the transaction if it is not forwarded yet
+    #[method(name = "dropTx")]
+    async fn drop_tx(&self, chain_id: u64, hash: TxHash) -> ApiResult<bool>;
+
+    /// Drop the sender queue with all its transactions
+    #[method(name = "dropSender")]
+    async fn drop_sender(&self, chain_id: u64, sender: String) -> ApiResult<()>;
+
+    /// Forward the transaction again on the next txpool update
+    #[method(name = "resendTx")]
+    async fn resend_tx(&self, chain_id: u64, hash: TxHash) -> ApiResult<bool>;
+
+    /// Pause forwarding of the sender or of all senders
+    #[method(name = "pause")]
+    async fn pause(&self, chain_id: u64, sender: Option<String>) -> ApiResult<()>;
+
+    /// Resume forwarding of the sender or of all senders
+    #[method(name = "resume")]
+    async fn resume(&self, chain_id: u64, sender: Option<String>) -> ApiResult<()>;
+
+    /// Health, latency and counters of every Solana RPC client
+    #[method(name = "solanaClients")]
+    async fn solana_clients(&self, chain_id: u64) -> ApiResult<Vec<ClientHealth>>;
+
+    /// Transactions failed to be forwarded with all retries
+    #[method(name = "deadLetters")]
+    async fn dead_letters(&self, chain_id: u64) -> ApiResult<HashMap<TxHash, DeadLetter>>;
+
+    #[method(name = "deadLetter")]
+    async fn dead_letter(&self, chain_id: u64, hash: TxHash) -> ApiResult<Option<DeadLetter>>;
+
+    /// Forward the dead-lettered transaction again
+    #[method(name = "requeueDeadLetter")]
+    async fn requeue_dead_letter(&self, chain_id: u64, hash: TxHash) -> ApiResult<bool>;
+
+    /// Remove dead letters, all of them if no hashes are given
+    #[method(name = "purgeDeadLetters")]
+    async fn purge_dead_letters(
+        &self,
+        chain_id: u64,
+        hashes: Option<Vec<TxHash>>,
+    ) -> ApiResult<usize>;
+}
@@ -1,14 +1,264 @@
+use crate::mempool::MempoolConfig;
+use crate::mempool_sender::{OverflowPolicy, RetryPolicy, SenderConfig};
+use crate::policy::PolicyConfig;
+use crate::rome_sender::SubmissionConfig;
+use crate::shadow::ShadowReport;
+use crate::store::{StoreConfig, StoreRetention};
+use anyhow::bail;
+use rome_sdk::rome_evm_client::indexer::config::EthereumStorageConfig;
 use rome_sdk::rome_evm_client::PayerConfig;
-use rome_sdk::rome_geth::indexers::pending_txs::GethPendingTxsIndexer;
 use solana_sdk::commitment_config::CommitmentLevel;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
+const DEFAULT_MEMPOOL_TTL_SEC: u64 = 300;
+const DEFAULT_NONCE_GAP_TIMEOUT_SEC: u64 = 30;
+const DEFAULT_FEE_BUMP_PERCENT: u64 = 10;
+const DEFAULT_BATCH_SIZE: usize = 100;
+const DEFAULT_MEMPOOL_SHARDS: usize = 16;
+const DEFAULT_SENDER_CAPACITY: usize = 1_024;
+const DEFAULT_DROPPED_SENDERS_CAPACITY: usize = 1_024;
+const DEFAULT_EXPIRY_CAPACITY: usize = 65_536;
+const DEFAULT_SHADOW_REPORT: &str = "rhea-shadow-report.jsonl";
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_BACKOFF_BASE_MS: u64 = 2_000;
+const DEFAULT_BACKOFF_CAP_MS: u64 = 60_000;
+const DEFAULT_JITTER_PERCENT: u64 = 10;
+const DEFAULT_NONCE_RETENTION_SEC: u64 = 24 * 3600;
+const DEFAULT_DEAD_LETTER_RETENTION_SEC: u64 = 7 * 24 * 3600;
+const DEFAULT_GETH_RECONCILE_INTERVAL_MS: u64 = 30_000;
+
+/// Retries of transactions failed with transient errors
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct RetryConfig {
+    pub max_retries: Option<u32>,
+    /// Delay before the first retry, doubled for every next one
+    pub backoff_base_ms: Option<u64>,
+    /// Maximal delay between retries
+    pub backoff_cap_ms: Option<u64>,
+    /// Maximal random addition to the delay in percents of the delay
+    pub jitter_percent: Option<u64>,
+}
+
+/// How long the records of the mempool store are kept
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct RetentionConfig {
+    /// Last nonces of the senders without new transactions
+    pub nonce_retention_sec: Option<u64>,
+    /// Dead letters since the last failure
+    pub dead_letter_retention_sec: Option<u64>,
+}
+
+/// Capacities of the internal channels
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct ChannelsConfig {
+    /// Events buffered and transactions queued per sender
+    pub sender_capacity: Option<usize>,
+    /// Handling of transactions for the full sender queue
+    pub sender_overflow: Option<OverflowPolicy>,
+    pub dropped_senders_capacity: Option<usize>,
+    /// Transactions waiting to be scheduled for expiry, ingestion waits if it is full
+    pub expiry_capacity: Option<usize>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RheaMode {
+    /// Forward transactions to Rome-EVM
+    #[default]
+    Live,
+    /// Compose and emulate transactions without sending them, results are written to the report
+    Shadow,
+}
+
+/// Geth txpool polled with `txpool_content`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct GethIndexerConfig {
+    pub geth_http_addr: url::Url,
+    pub geth_poll_interval_ms: u64,
+}
+
+impl GethIndexerConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.geth_poll_interval_ms)
+    }
+}
+
+/// Rollup served by Rhea
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
-pub struct RheaConfig {
-    pub rpc_urls: Vec<url::Url>,
-    pub commitment: CommitmentLevel,
-    pub program_id: String,
+pub struct RollupConfig {
     pub chain_id: u64,
+    pub program_id: String,
+    /// Payers of the Rome transactions of the rollup
     pub payers: Vec<PayerConfig>,
-    pub geth_indexer: GethPendingTxsIndexer,
+    /// Block storage of the Rome-EVM clients reading the rollup state, in memory if not set
+    pub ethereum_storage: Option<EthereumStorageConfig>,
+    /// Gas price of the Rome-EVM clients reading the rollup state, 1 gwei by default
+    pub gas_price: Option<u128>,
+    pub geth_indexer: GethIndexerConfig,
+    /// Geth WebSocket endpoint to subscribe to new pending transactions.
+    /// The txpool is polled over it every `geth_reconcile_interval_ms` instead of the geth indexer
+    /// to reconcile missed transactions.
+    pub geth_ws_url: Option<url::Url>,
+    pub geth_reconcile_interval_ms: Option<u64>,
     pub mempool_ttl: Option<u64>,
+    pub store: Option<StoreConfig>,
+    /// Admission policy, reloaded when the config file changes
+    #[serde(default)]
+    pub policy: PolicyConfig,
+}
+
+impl RollupConfig {
+    pub fn geth_reconcile_interval(&self) -> Duration {
+        Duration::from_millis(
+            self.geth_reconcile_interval_ms
+                .unwrap_or(DEFAULT_GETH_RECONCILE_INTERVAL_MS),
+        )
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct RheaConfig {
+    #[serde(default)]
+    pub mode: RheaMode,
+    /// Path of the shadow mode report
+    pub shadow_report: Option<PathBuf>,
+    pub rpc_urls: Vec<url::Url>,
+    pub commitment: CommitmentLevel,
+    #[serde(default)]
+    pub rollups: Vec<RollupConfig>,
+    /// Fields of the single rollup at the top level, the format before `rollups`
+    #[serde(flatten)]
+    pub single_rollup: Option<RollupConfig>,
+    /// Seconds to wait for a missing nonce before dropping transactions of the sender
+    pub nonce_gap_timeout: Option<u64>,
+    /// Forward transactions queued in geth without waiting for them to become executable
+    pub forward_queued: Option<bool>,
+    /// Minimal fee increase in percents for a transaction to replace the pending one
+    pub fee_bump_percent: Option<u64>,
+    /// Maximal number of transactions of the sender received at once
+    pub batch_size: Option<usize>,
+    /// Number of independently locked parts of the mempool
+    pub mempool_shards: Option<usize>,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub channels: ChannelsConfig,
+    /// Retention of the records of the mempool stores
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// Submission of transactions through the Solana RPCs of `rpc_urls`
+    #[serde(default)]
+    pub submission: SubmissionConfig,
+    /// Address of the admin JSON RPC server
+    pub admin_rpc: Option<SocketAddr>,
+    /// Address of the HTTP server serving Prometheus metrics on /metrics
+    pub metrics_host: Option<SocketAddr>,
+}
+
+impl RheaConfig {
+    /// Take the served rollups, either `rollups` or the single rollup at the top level
+    pub fn take_rollups(&mut self) -> anyhow::Result<Vec<RollupConfig>> {
+        match self.single_rollup.take() {
+            Some(_) if !self.rollups.is_empty() => {
+                bail!("Rollup fields are set both at the top level and in `rollups`")
+            }
+            Some(rollup) => {
+                tracing::warn!(
+                    "Rollup {} is configured at the top level, move it to `rollups`",
+                    rollup.chain_id
+                );
+                Ok(vec![rollup])
+            }
+            None if self.rollups.is_empty() => bail!(
+                "No rollups configured: set `rollups` or chain_id, program_id, payers \
+                 and geth_indexer of the single rollup"
+            ),
+            None => Ok(std::mem::take(&mut self.rollups)),
+        }
+    }
+
+    /// Open the report if Rhea runs in the shadow mode
+    pub async fn shadow_report(&self) -> anyhow::Result<Option<Arc<ShadowReport>>> {
+        if self.mode != RheaMode::Shadow {
+            return Ok(None);
+        }
+
+        let path = self
+            .shadow_report
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_SHADOW_REPORT));
+        tracing::warn!(
+            "Rhea runs in the shadow mode, transactions are not sent, see the report at {:?}",
+            path
+        );
+
+        Ok(Some(Arc::new(ShadowReport::open(&path).await?)))
+    }
+
+    pub fn store_retention(&self, rollup: &RollupConfig) -> StoreRetention {
+        let mempool_ttl = rollup.mempool_ttl.unwrap_or(DEFAULT_MEMPOOL_TTL_SEC);
+
+        StoreRetention {
+            tx_ttl: Duration::from_secs(2 * mempool_ttl),
+            nonce_ttl: Duration::from_secs(
+                self.retention
+                    .nonce_retention_sec
+                    .unwrap_or(DEFAULT_NONCE_RETENTION_SEC),
+            ),
+            dead_letter_ttl: Duration::from_secs(
+                self.retention
+                    .dead_letter_retention_sec
+                    .unwrap_or(DEFAULT_DEAD_LETTER_RETENTION_SEC),
+            ),
+        }
+    }
+
+    pub fn mempool_config(&self, rollup: &RollupConfig) -> MempoolConfig {
+        let mempool_ttl =
+            Duration::from_secs(rollup.mempool_ttl.unwrap_or(DEFAULT_MEMPOOL_TTL_SEC));
+
+        MempoolConfig {
+            mempool_ttl,
+            forward_queued: self.forward_queued.unwrap_or(false),
+            shards: self.mempool_shards.unwrap_or(DEFAULT_MEMPOOL_SHARDS),
+            dropped_senders_capacity: self
+                .channels
+                .dropped_senders_capacity
+                .unwrap_or(DEFAULT_DROPPED_SENDERS_CAPACITY),
+            expiry_capacity: self
+                .channels
+                .expiry_capacity
+                .unwrap_or(DEFAULT_EXPIRY_CAPACITY),
+            sender: SenderConfig {
+                chain_id: rollup.chain_id,
+                sender_ttl: mempool_ttl,
+                gap_timeout: Duration::from_secs(
+                    self.nonce_gap_timeout
+                        .unwrap_or(DEFAULT_NONCE_GAP_TIMEOUT_SEC),
+                ),
+                fee_bump_percent: self.fee_bump_percent.unwrap_or(DEFAULT_FEE_BUMP_PERCENT),
+                batch_size: self.batch_size.unwrap_or(DEFAULT_BATCH_SIZE),
+                queue_capacity: self
+                    .channels
+                    .sender_capacity
+                    .unwrap_or(DEFAULT_SENDER_CAPACITY),
+                overflow: self.channels.sender_overflow.unwrap_or_default(),
+                retry: RetryPolicy {
+                    max_retries: self.retry.max_retries.unwrap_or(DEFAULT_MAX_RETRIES),
+                    backoff_base: Duration::from_millis(
+                        self.retry
+                            .backoff_base_ms
+                            .unwrap_or(DEFAULT_BACKOFF_BASE_MS),
+                    ),
+                    backoff_cap: Duration::from_millis(
+                        self.retry.backoff_cap_ms.unwrap_or(DEFAULT_BACKOFF_CAP_MS),
+                    ),
+                    jitter_percent: self.retry.jitter_percent.unwrap_or(DEFAULT_JITTER_PERCENT),
+                },
+            },
+        }
+    }
 }
@@ -0,0 +1,11 @@
+pub mod api;
+pub mod cli;
+pub mod config;
+pub mod mempool;
+pub mod mempool_sender;
+pub mod metrics;
+pub mod policy;
+pub mod rome_sender;
+pub mod service;
+pub mod shadow;
+pub mod store;
@@ -1,23 +1,15 @@
-use clap::Parser;
-use tokio::signal;
-
-use self::cli::Cli;
-use self::config::RheaConfig;
-use self::service::RheaService;
-use crate::rome_sender::RomeSender;
 use anyhow::{anyhow, bail};
+use clap::Parser;
 use dotenv::dotenv;
+use rhea::cli::Cli;
+use rhea::config::RheaConfig;
+use rhea::metrics;
+use rhea::policy::Policy;
+use rhea::rome_sender::RomeSender;
+use rhea::service::{RheaService, Rollup};
 use rome_obs::Otel;
-use solana_sdk::pubkey::Pubkey;
-use std::str::FromStr;
-use std::time::Duration;
-
-mod cli;
-mod config;
-mod mempool;
-mod mempool_sender;
-mod rome_sender;
-mod service;
+use std::sync::Arc;
+use tokio::signal;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -25,23 +17,42 @@ async fn main() -> anyhow::Result<()> {
 
     Otel::init_from_env("rhea").map_err(|e| anyhow!(e.to_string()))?;
 
-    let config: RheaConfig = Cli::parse().load_config().await?;
-    let program_id = Pubkey::from_str(&config.program_id)?;
+    let cli = Cli::parse();
+    let mut config: RheaConfig = cli.load_config().await?;
+    if let Some(host) = config.metrics_host {
+        metrics::start_http_server(host).await?;
+    }
 
-    let rhea_service_jh = RheaService::start(
-        RomeSender::new(
-            config.rpc_urls,
+    let shadow_report = config.shadow_report().await?;
+    let rollup_configs = config.take_rollups()?;
+    let mut rollups = Vec::with_capacity(rollup_configs.len());
+    for rollup in rollup_configs {
+        let mempool_config = config.mempool_config(&rollup);
+        let store_retention = config.store_retention(&rollup);
+        let geth_reconcile_interval = rollup.geth_reconcile_interval();
+        let rome = RomeSender::new(
+            config.rpc_urls.clone(),
             config.commitment,
-            &vec![(config.chain_id, program_id.to_string())]
-                .into_iter()
-                .collect(),
-            &config.payers,
+            &rollup,
+            config.submission.clone(),
+            shadow_report.clone(),
         )
-        .await
-        .unwrap(),
-        config.geth_indexer,
-        config.mempool_ttl.map(Duration::from_secs),
-    );
+        .await?;
+
+        rollups.push(Rollup {
+            chain_id: rollup.chain_id,
+            rome,
+            geth_indexer: rollup.geth_indexer,
+            geth_ws_url: rollup.geth_ws_url,
+            geth_reconcile_interval,
+            mempool_config,
+            store_config: rollup.store,
+            store_retention,
+            policy: Arc::new(Policy::new(rollup.chain_id, rollup.policy)),
+        });
+    }
+
+    let rhea_service_jh = RheaService::start(rollups, config.admin_rpc, cli.get_config_path()?);
 
     tokio::select! {
         res = rhea_service_jh => {
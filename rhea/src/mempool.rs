@@ -1,154 +1,915 @@
-use crate::mempool_sender::MempoolSender;
-use crate::rome_sender::RomeSender;
-use ethers::types::TxHash;
+use crate::mempool_sender::{
+    MempoolSender, OverflowPolicy, QueuedTx, SenderConfig, SenderEvent, SenderHandle, SenderInfo,
+};
+use crate::metrics::{
+    ACTIVE_SENDERS, CHANNEL_OCCUPANCY, CHANNEL_OVERFLOWS, TXS_FAILED, TXS_RECEIVED,
+};
+use crate::policy::Policy;
+use crate::rome_sender::TxSender;
+use crate::store::{DeadLetter, MempoolStore, StoredState, StoredTx, TxStatus};
+use ethers::types::{Transaction, TxHash};
+use ethers::utils::to_checksum;
+use futures::{FutureExt, StreamExt};
+use prometheus::IntGauge;
 use rome_sdk::rome_geth::types::{GethTxPoolResult, GethTxPoolTx};
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::Mutex;
+use tokio_util::time::DelayQueue;
 
-struct MempoolImpl {
-    transactions: HashMap<TxHash, (String, u64)>,
-    senders: HashMap<String, UnboundedSender<(u64, GethTxPoolTx)>>,
-    sender_ttl: Duration,
-    rome: Arc<RomeSender>,
-    drop_sender_tx: UnboundedSender<String>,
+#[derive(Debug, Clone)]
+pub struct MempoolConfig {
+    /// Transactions are forgotten after this time
+    pub mempool_ttl: Duration,
+    /// Forward queued transactions without waiting for geth to promote them to pending
+    pub forward_queued: bool,
+    /// Number of independently locked parts of the mempool, senders are distributed between them
+    pub shards: usize,
+    /// Capacity of the channel of sender queues to be forgotten
+    pub dropped_senders_capacity: usize,
+    /// Capacity of the channel of transactions to be scheduled for expiry
+    pub expiry_capacity: usize,
+    pub sender: SenderConfig,
+}
+
+/// State shared by all shards
+struct MempoolContext {
+    rome: Arc<dyn TxSender>,
+    sender_config: SenderConfig,
+    forward_queued: bool,
+    /// Forwarding is paused for all senders
+    paused: AtomicBool,
+    drop_sender_tx: mpsc::Sender<String>,
+    store: Arc<MempoolStore>,
+    policy: Arc<Policy>,
 }
 
-impl MempoolImpl {
-    pub fn new(
-        rome: Arc<RomeSender>,
-        sender_ttl: Duration,
-        drop_sender_tx: UnboundedSender<String>,
-    ) -> Self {
-        Self {
-            transactions: HashMap::new(),
-            senders: HashMap::new(),
-            rome,
-            sender_ttl,
-            drop_sender_tx,
+impl MempoolContext {
+    fn chain_id(&self) -> String {
+        self.sender_config.chain_id.to_string()
+    }
+
+    fn channel_occupancy(&self, channel: &str) -> IntGauge {
+        CHANNEL_OCCUPANCY.with_label_values(&[&self.chain_id(), channel])
+    }
+
+    fn active_senders(&self) -> IntGauge {
+        ACTIVE_SENDERS.with_label_values(&[&self.chain_id()])
+    }
+
+    /// Pass the control event to the sender queue, waits if the queue is full
+    async fn send_event(&self, events: &mpsc::Sender<SenderEvent>, event: SenderEvent) -> bool {
+        let sent = events.send(event).await.is_ok();
+        if sent {
+            self.channel_occupancy("senders").inc();
         }
+
+        sent
     }
+}
 
-    pub async fn update(&mut self, geth_txs: &GethTxPoolResult) -> Vec<TxHash> {
-        let mut new_txs = Vec::with_capacity(geth_txs.queued.len() + geth_txs.pending.len());
+/// Transactions from the txpool update belonging to one shard
+#[derive(Default)]
+struct ShardUpdate<'a> {
+    pending: Vec<(&'a String, u64, &'a GethTxPoolTx)>,
+    queued: Vec<(&'a String, u64, &'a GethTxPoolTx)>,
+}
 
-        for (sender, queued_txs) in geth_txs.queued.iter() {
-            for (nonce, tx) in queued_txs {
-                if self.add_tx(sender.clone(), *nonce, tx.clone()).await {
+/// Transaction of the full sender queue with the `pause` overflow policy,
+/// passed to the queue once the shard is unlocked
+struct HeldTx {
+    sender: String,
+    hash: TxHash,
+    events: mpsc::Sender<SenderEvent>,
+    event: SenderEvent,
+}
+
+/// Transactions and sender queues of a part of the senders
+#[derive(Default)]
+struct MempoolShard {
+    transactions: HashMap<TxHash, (String, u64)>,
+    /// Queued transactions waiting to become executable,
+    /// or forwarded before becoming executable with `forward_queued`
+    queued: HashSet<TxHash>,
+    senders: HashMap<String, SenderHandle>,
+    /// Senders paused by the operator, kept when their queues are dropped and created again
+    paused_senders: HashSet<String>,
+    /// Transactions waiting for their full sender queues, taken by the mempool after every change
+    held: Vec<HeldTx>,
+}
+
+impl MempoolShard {
+    async fn update(&mut self, ctx: &MempoolContext, update: ShardUpdate<'_>) -> Vec<TxHash> {
+        let mut new_txs = Vec::with_capacity(update.queued.len() + update.pending.len());
+
+        if ctx.forward_queued {
+            // The sender queue waits for the missing nonces up to the nonce gap timeout
+            for (sender, nonce, tx) in update.queued {
+                if self
+                    .add_tx(ctx, tx.hash, sender.clone(), nonce, || {
+                        QueuedTx::from_geth(tx)
+                    })
+                    .await
+                {
+                    self.queued.insert(tx.hash);
                     new_txs.push(tx.hash);
                 }
             }
+        } else {
+            // Queued transactions are not executable until geth promotes them to pending
+            self.queued = update
+                .queued
+                .iter()
+                .map(|(_, _, tx)| tx.hash)
+                .filter(|hash| !self.transactions.contains_key(hash))
+                .collect();
         }
 
-        for (sender, queued_txs) in geth_txs.pending.iter() {
-            for (nonce, tx) in queued_txs {
-                if self.add_tx(sender.clone(), *nonce, tx.clone()).await {
-                    new_txs.push(tx.hash);
+        for (sender, nonce, tx) in update.pending {
+            if self.queued.remove(&tx.hash) {
+                tracing::info!("Queued tx {:?} became executable", tx.hash);
+                if ctx.forward_queued && self.failed_while_queued(ctx, &tx.hash) {
+                    // Forwarded again now that its predecessors are in the txpool
+                    self.transactions.remove(&tx.hash);
                 }
             }
+
+            if self
+                .add_tx(ctx, tx.hash, sender.clone(), nonce, || {
+                    QueuedTx::from_geth(tx)
+                })
+                .await
+            {
+                new_txs.push(tx.hash);
+            }
         }
 
         new_txs
     }
 
-    pub async fn remove_txs(&mut self, txs: Vec<TxHash>) {
-        for tx in &txs {
+    /// The transaction forwarded while queued has failed, e.g. on the nonce gap timeout.
+    /// Dead letters failed to be sent are not forwarded again.
+    fn failed_while_queued(&self, ctx: &MempoolContext, hash: &TxHash) -> bool {
+        ctx.store
+            .tx(hash)
+            .is_some_and(|tx| tx.status == TxStatus::Failed)
+            && ctx.store.dead_letter(hash).is_none()
+    }
+
+    async fn remove_txs(&mut self, ctx: &MempoolContext, txs: &[TxHash]) {
+        for tx in txs {
             self.transactions.remove(tx);
+            self.queued.remove(tx);
+        }
+        ctx.store.remove_txs(txs).await;
+    }
+
+    #[tracing::instrument(name = "rhea::remove_sender", skip(self, ctx))]
+    fn remove_sender(&mut self, ctx: &MempoolContext, sender_address: &str) -> bool {
+        let removed = self.senders.remove(sender_address).is_some();
+        if removed {
+            ctx.active_senders().dec();
         }
+
+        removed
     }
 
-    #[tracing::instrument(name = "rhea::remove_sender", skip(self))]
-    pub async fn remove_sender(&mut self, sender_address: String) {
-        self.senders.remove(&sender_address);
+    /// Drop the transaction if it is not forwarded yet
+    async fn drop_tx(&self, ctx: &MempoolContext, hash: &TxHash) -> bool {
+        let Some(handle) = self
+            .transactions
+            .get(hash)
+            .and_then(|(sender, _)| self.senders.get(sender))
+        else {
+            return false;
+        };
+
+        ctx.send_event(&handle.events, SenderEvent::DropTx(*hash))
+            .await
     }
 
-    #[tracing::instrument(name = "rhea::add_tx", skip(self), fields(tx_hash = ?tx.hash))]
-    async fn add_tx(&mut self, sender: String, nonce: u64, tx: GethTxPoolTx) -> bool {
-        let tx_hash = tx.hash;
+    #[tracing::instrument(name = "rhea::add_tx", skip(self, ctx, decode))]
+    async fn add_tx(
+        &mut self,
+        ctx: &MempoolContext,
+        tx_hash: TxHash,
+        sender: String,
+        nonce: u64,
+        decode: impl FnOnce() -> anyhow::Result<QueuedTx>,
+    ) -> bool {
         let None = self.transactions.insert(tx_hash, (sender.clone(), nonce)) else {
             // Transaction already known
             return false;
         };
+        let rejected_before = ctx
+            .store
+            .tx(&tx_hash)
+            .is_some_and(|tx| tx.status == TxStatus::Rejected);
+        if !rejected_before {
+            ctx.store.tx_received(tx_hash, sender.clone(), nonce).await;
+            TXS_RECEIVED.with_label_values(&[&ctx.chain_id()]).inc();
+        }
 
-        let Err(err) = self
+        match decode() {
+            Ok(tx) => {
+                let admitted = match rejected_before {
+                    // Rejections are logged and counted once
+                    true => ctx.policy.check(&sender, &tx),
+                    false => ctx.policy.admit(tx_hash, &sender, &tx),
+                };
+                if admitted.is_err() {
+                    // Forgotten to be checked again on the next txpool update,
+                    // its successors wait for it in the sender queue meanwhile.
+                    // Not scheduled to expire, the store prunes the rejected status.
+                    self.transactions.remove(&tx_hash);
+                    if !rejected_before {
+                        ctx.store.tx_rejected(tx_hash).await;
+                    }
+                    return false;
+                }
+
+                self.submit(ctx, sender, nonce, tx).await
+            }
+            Err(err) => {
+                // Known as failed until expired
+                tracing::warn!("Failed to add tx {} to mempool: {:?}", tx_hash, err);
+                TXS_FAILED
+                    .with_label_values(&[&ctx.chain_id(), "invalid"])
+                    .inc();
+                ctx.store.tx_failed(tx_hash).await;
+                true
+            }
+        }
+    }
+
+    /// Pass the transaction to the sender queue, the overflow policy applies if it is full
+    async fn submit(
+        &mut self,
+        ctx: &MempoolContext,
+        sender: String,
+        nonce: u64,
+        tx: QueuedTx,
+    ) -> bool {
+        let tx_hash = tx.hash;
+        let events = self
             .senders
             .entry(sender.clone())
             .or_insert_with(|| {
+                ctx.active_senders().inc();
                 MempoolSender::init(
                     sender.clone(),
-                    self.rome.clone(),
-                    self.sender_ttl,
-                    self.drop_sender_tx.clone(),
+                    ctx.rome.clone(),
+                    ctx.sender_config.clone(),
+                    ctx.drop_sender_tx.clone(),
+                    ctx.store.clone(),
+                    ctx.paused.load(Ordering::Relaxed) || self.paused_senders.contains(&sender),
                 )
             })
-            .send((nonce, tx))
-        else {
-            // Transaction added to mempool
-            return true;
+            .events
+            .clone();
+
+        let event = match events.try_send(SenderEvent::Tx(nonce, Box::new(tx))) {
+            Ok(()) => {
+                // Transaction added to mempool
+                ctx.channel_occupancy("senders").inc();
+                return true;
+            }
+            Err(TrySendError::Full(event)) => event,
+            Err(TrySendError::Closed(_)) => {
+                self.sender_closed(ctx, &sender, tx_hash).await;
+                return false;
+            }
         };
 
-        // Failed to add transaction to mempool
-        self.senders.remove(&sender);
-        self.transactions.remove(&tx_hash);
+        let overflow = ctx.sender_config.overflow;
+        CHANNEL_OVERFLOWS
+            .with_label_values(&[&ctx.chain_id(), "senders", overflow.as_str()])
+            .inc();
         tracing::warn!(
-            "Failed to add tx {} to mempool sender {}: {:?}",
-            tx_hash,
+            "Sender queue {} is full, tx {} is handled by the {} policy",
             sender,
-            err
+            tx_hash,
+            overflow.as_str()
         );
 
-        false
+        match overflow {
+            OverflowPolicy::Pause => {
+                // Waiting for the queue under the lock would block the other senders of the shard
+                self.held.push(HeldTx {
+                    sender,
+                    hash: tx_hash,
+                    events,
+                    event,
+                });
+                true
+            }
+            OverflowPolicy::DropSender | OverflowPolicy::Reject => {
+                if overflow == OverflowPolicy::DropSender {
+                    self.remove_sender(ctx, &sender);
+                }
+
+                // Known as failed until expired
+                TXS_FAILED
+                    .with_label_values(&[&ctx.chain_id(), "queue_full"])
+                    .inc();
+                ctx.store.tx_failed(tx_hash).await;
+                true
+            }
+        }
+    }
+
+    /// The sender queue has stopped, the transaction is received from the txpool again
+    async fn sender_closed(&mut self, ctx: &MempoolContext, sender: &str, tx_hash: TxHash) {
+        self.remove_sender(ctx, sender);
+        self.remove_txs(ctx, &[tx_hash]).await;
+        tracing::warn!(
+            "Failed to add tx {} to mempool sender {}: the queue is closed",
+            tx_hash,
+            sender
+        );
     }
 }
 
 pub struct Mempool {
-    inner: Arc<Mutex<MempoolImpl>>,
+    shards: Vec<Mutex<MempoolShard>>,
+    ctx: MempoolContext,
     mempool_ttl: Duration,
+    /// Transactions scheduled to be forgotten after the mempool TTL
+    expiry_tx: mpsc::Sender<(usize, TxHash)>,
 }
 
-async fn process_dropped_senders(
-    mut drop_sender_rx: UnboundedReceiver<String>,
-    mempool_impl: Arc<Mutex<MempoolImpl>>,
-) {
-    while let Some(sender_address) = drop_sender_rx.recv().await {
-        tracing::info!("Removing sender {}", sender_address);
-        mempool_impl
+impl Mempool {
+    pub async fn new(
+        rome: Arc<dyn TxSender>,
+        config: MempoolConfig,
+        store: Arc<MempoolStore>,
+        stored_state: StoredState,
+        policy: Arc<Policy>,
+    ) -> Arc<Self> {
+        let (drop_sender_tx, drop_sender_rx) = mpsc::channel(config.dropped_senders_capacity);
+        let (expiry_tx, expiry_rx) = mpsc::channel(config.expiry_capacity);
+        let mut shards = Vec::new();
+        shards.resize_with(config.shards.max(1), MempoolShard::default);
+
+        // Transactions processed before the restart stay known until expired,
+        // rejected ones are checked again
+        let mut known_txs = Vec::with_capacity(stored_state.txs.len());
+        for (hash, tx) in stored_state.txs {
+            if tx.status == TxStatus::Rejected {
+                continue;
+            }
+            let index = shard_index(&tx.sender, shards.len());
+            shards[index]
+                .transactions
+                .insert(hash, (tx.sender, tx.nonce));
+            known_txs.push((index, hash));
+        }
+
+        let mempool = Arc::new(Self {
+            shards: shards.into_iter().map(Mutex::new).collect(),
+            ctx: MempoolContext {
+                rome,
+                sender_config: config.sender,
+                forward_queued: config.forward_queued,
+                paused: AtomicBool::new(false),
+                drop_sender_tx,
+                store,
+                policy,
+            },
+            mempool_ttl: config.mempool_ttl,
+            expiry_tx,
+        });
+
+        tokio::spawn(mempool.clone().process_dropped_senders(drop_sender_rx));
+        tokio::spawn(mempool.clone().expiry_task(expiry_rx));
+        mempool.expire(known_txs).await;
+
+        mempool
+    }
+
+    fn shard(&self, sender: &str) -> (usize, &Mutex<MempoolShard>) {
+        let index = shard_index(sender, self.shards.len());
+        (index, &self.shards[index])
+    }
+
+    pub async fn update(&self, geth_txs: &GethTxPoolResult) {
+        let mut updates = Vec::new();
+        updates.resize_with(self.shards.len(), ShardUpdate::default);
+        for (sender, pending_txs) in geth_txs.pending.iter() {
+            let update = &mut updates[shard_index(sender, self.shards.len())];
+            for (nonce, tx) in pending_txs {
+                update.pending.push((sender, *nonce, tx));
+            }
+        }
+        for (sender, queued_txs) in geth_txs.queued.iter() {
+            let update = &mut updates[shard_index(sender, self.shards.len())];
+            for (nonce, tx) in queued_txs {
+                update.queued.push((sender, *nonce, tx));
+            }
+        }
+
+        for (index, update) in updates.into_iter().enumerate() {
+            let (txs, held) = {
+                let mut shard = self.shards[index].lock().await;
+                let txs = shard.update(&self.ctx, update).await;
+                (txs, std::mem::take(&mut shard.held))
+            };
+            self.pass_held(index, held).await;
+            self.expire(txs.into_iter().map(|hash| (index, hash)).collect())
+                .await;
+        }
+    }
+
+    /// Pass transactions held by the full sender queues, waits until the queues take them
+    async fn pass_held(&self, index: usize, held: Vec<HeldTx>) {
+        for held in held {
+            if !self.ctx.send_event(&held.events, held.event).await {
+                self.shards[index]
+                    .lock()
+                    .await
+                    .sender_closed(&self.ctx, &held.sender, held.hash)
+                    .await;
+            }
+        }
+    }
+
+    /// Add the transaction received from the geth subscription
+    pub async fn add_tx(&self, tx: &Transaction) {
+        // Geth txpool uses checksummed sender addresses
+        let sender = to_checksum(&tx.from, None);
+        let (index, shard) = self.shard(&sender);
+        let (added, held) = {
+            let mut shard = shard.lock().await;
+            let added = shard
+                .add_tx(&self.ctx, tx.hash, sender, tx.nonce.as_u64(), || {
+                    QueuedTx::from_raw(&tx.rlp())
+                })
+                .await;
+            (added, std::mem::take(&mut shard.held))
+        };
+        self.pass_held(index, held).await;
+        if added {
+            self.expire(vec![(index, tx.hash)]).await;
+        }
+    }
+
+    async fn process_dropped_senders(self: Arc<Self>, mut drop_sender_rx: mpsc::Receiver<String>) {
+        while let Some(sender_address) = drop_sender_rx.recv().await {
+            self.ctx
+                .channel_occupancy("dropped_senders")
+                .set(drop_sender_rx.len() as i64);
+            tracing::info!("Removing sender {}", sender_address);
+            self.shard(&sender_address)
+                .1
+                .lock()
+                .await
+                .remove_sender(&self.ctx, &sender_address);
+        }
+    }
+
+    pub async fn senders(&self) -> HashMap<String, SenderInfo> {
+        let mut senders = HashMap::new();
+        for shard in &self.shards {
+            for (sender, handle) in &shard.lock().await.senders {
+                let info = handle.info.lock().expect("sender info lock poisoned");
+                senders.insert(sender.clone(), info.clone());
+            }
+        }
+
+        senders
+    }
+
+    pub fn tx_status(&self, hash: &TxHash) -> Option<StoredTx> {
+        self.ctx.store.tx(hash)
+    }
+
+    /// Drop the transaction if it is not forwarded yet
+    pub async fn drop_tx(&self, hash: &TxHash) -> bool {
+        let Some(tx) = self.ctx.store.tx(hash) else {
+            return false;
+        };
+
+        self.shard(&tx.sender)
+            .1
+            .lock()
+            .await
+            .drop_tx(&self.ctx, hash)
+            .await
+    }
+
+    pub async fn drop_sender(&self, sender: &str) -> bool {
+        let dropped = self
+            .shard(sender)
+            .1
             .lock()
             .await
-            .remove_sender(sender_address)
+            .remove_sender(&self.ctx, sender);
+        if dropped {
+            tracing::info!("Sender {} dropped by the operator", sender);
+        }
+
+        dropped
+    }
+
+    /// Forget the transaction so it is forwarded again on the next txpool update
+    pub async fn resend_tx(&self, hash: TxHash) -> bool {
+        let Some(tx) = self.ctx.store.tx(&hash) else {
+            return false;
+        };
+
+        let mut shard = self.shard(&tx.sender).1.lock().await;
+        let known = shard.transactions.contains_key(&hash);
+        if known {
+            shard.remove_txs(&self.ctx, &[hash]).await;
+        }
+
+        known
+    }
+
+    /// Pause or resume forwarding of the sender or of all senders.
+    /// Resuming all senders also resumes the individually paused ones.
+    pub async fn pause(&self, sender: Option<&str>, paused: bool) {
+        let Some(sender) = sender else {
+            self.ctx.paused.store(paused, Ordering::Relaxed);
+            for shard in &self.shards {
+                let mut shard = shard.lock().await;
+                if !paused {
+                    shard.paused_senders.clear();
+                }
+                for handle in shard.senders.values() {
+                    self.ctx
+                        .send_event(&handle.events, SenderEvent::Pause(paused))
+                        .await;
+                }
+            }
+            return;
+        };
+
+        // The sender without a queue starts paused once its transactions arrive
+        let mut shard = self.shard(sender).1.lock().await;
+        if paused {
+            shard.paused_senders.insert(sender.to_string());
+        } else {
+            shard.paused_senders.remove(sender);
+        }
+
+        if let Some(handle) = shard.senders.get(sender) {
+            self.ctx
+                .send_event(&handle.events, SenderEvent::Pause(paused))
+                .await;
+        }
+    }
+
+    pub fn dead_letters(&self) -> HashMap<TxHash, DeadLetter> {
+        self.ctx.store.dead_letters()
+    }
+
+    pub fn dead_letter(&self, hash: &TxHash) -> Option<DeadLetter> {
+        self.ctx.store.dead_letter(hash)
+    }
+
+    /// Forward the dead-lettered transaction again, returns false if it is unknown
+    pub async fn requeue_dead_letter(&self, hash: TxHash) -> anyhow::Result<bool> {
+        let Some(letter) = self.ctx.store.dead_letter(&hash) else {
+            return Ok(false);
+        };
+
+        let mut tx = QueuedTx::from_raw(&letter.raw_tx)?;
+        tx.received_at = letter.first_seen;
+
+        let (index, shard) = self.shard(&letter.sender);
+        let mut shard = shard.lock().await;
+        shard
+            .transactions
+            .insert(hash, (letter.sender.clone(), letter.nonce));
+        self.ctx
+            .store
+            .tx_received(hash, letter.sender.clone(), letter.nonce)
             .await;
+        self.ctx.store.purge_dead_letter(hash).await;
+
+        let requeued = shard
+            .submit(&self.ctx, letter.sender, letter.nonce, tx)
+            .await;
+        let held = std::mem::take(&mut shard.held);
+        // The expiry scheduler and the full sender queue may wait for the shard
+        drop(shard);
+        self.pass_held(index, held).await;
+        self.expire(vec![(index, hash)]).await;
+        Ok(requeued)
+    }
+
+    /// Remove dead letters, all of them if no hashes are given
+    pub async fn purge_dead_letters(&self, hashes: Option<Vec<TxHash>>) -> usize {
+        let hashes = hashes.unwrap_or_else(|| {
+            self.ctx
+                .store
+                .dead_letters()
+                .into_keys()
+                .collect::<Vec<_>>()
+        });
+
+        let mut purged = 0;
+        for hash in hashes {
+            if self.ctx.store.purge_dead_letter(hash).await {
+                purged += 1;
+            }
+        }
+
+        purged
+    }
+
+    /// Forget transactions after the mempool TTL, waits if the scheduler is behind
+    async fn expire(&self, txs: Vec<(usize, TxHash)>) {
+        for tx in txs {
+            if self.expiry_tx.send(tx).await.is_err() {
+                tracing::warn!("Mempool expiry scheduler is stopped");
+                return;
+            }
+        }
+    }
+
+    /// Single timer of all transactions scheduled to be forgotten
+    async fn expiry_task(self: Arc<Self>, mut expiry_rx: mpsc::Receiver<(usize, TxHash)>) {
+        let mut queue = DelayQueue::new();
+        loop {
+            tokio::select! {
+                scheduled = expiry_rx.recv() => match scheduled {
+                    Some(tx) => {
+                        self.ctx
+                            .channel_occupancy("expiry")
+                            .set(expiry_rx.len() as i64);
+                        queue.insert(tx, self.mempool_ttl);
+                    }
+                    None => return,
+                },
+                Some(expired) = queue.next(), if !queue.is_empty() => {
+                    // Remove all expired transactions at once
+                    let mut expired_txs = vec![expired.into_inner()];
+                    while let Some(Some(expired)) = queue.next().now_or_never() {
+                        expired_txs.push(expired.into_inner());
+                    }
+                    self.remove_expired(expired_txs).await;
+                }
+            }
+        }
+    }
+
+    async fn remove_expired(&self, txs: Vec<(usize, TxHash)>) {
+        let mut by_shard: HashMap<usize, Vec<TxHash>> = HashMap::new();
+        for (index, hash) in txs {
+            by_shard.entry(index).or_default().push(hash);
+        }
+
+        for (index, hashes) in by_shard {
+            self.shards[index]
+                .lock()
+                .await
+                .remove_txs(&self.ctx, &hashes)
+                .await;
+        }
     }
 }
 
-impl Mempool {
-    pub fn new(rome: Arc<RomeSender>, mempool_ttl: Duration) -> Self {
-        let (drop_sender_tx, drop_sender_rx) = tokio::sync::mpsc::unbounded_channel();
-        let mempool_impl = Arc::new(Mutex::new(MempoolImpl::new(
-            rome,
-            mempool_ttl,
-            drop_sender_tx,
-        )));
+fn shard_index(sender: &str, shards: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    sender.hash(&mut hasher);
+    (hasher.finish() % shards as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mempool_sender::{OverflowPolicy, RetryPolicy};
+    use crate::policy::PolicyConfig;
+    use crate::rome_sender::SendError;
+    use crate::store::StoreRetention;
+    use async_trait::async_trait;
+    use ethers::types::Address;
+    use serde_json::json;
+    use std::str::FromStr;
 
-        tokio::spawn(process_dropped_senders(
-            drop_sender_rx,
-            mempool_impl.clone(),
-        ));
+    const CHAIN_ID: u64 = 1001;
+    const GAP_TIMEOUT: Duration = Duration::from_millis(200);
+
+    /// Accepts every transaction, the next nonce of the sender follows the last accepted one
+    #[derive(Default)]
+    struct StubSender {
+        sent: std::sync::Mutex<Vec<(Address, u64)>>,
+    }
 
-        Self {
-            inner: mempool_impl,
-            mempool_ttl,
+    impl StubSender {
+        fn sent(&self) -> Vec<u64> {
+            let sent = self.sent.lock().unwrap();
+            sent.iter().map(|(_, nonce)| *nonce).collect()
         }
     }
 
-    pub async fn update(&self, geth_txs: &GethTxPoolResult) {
-        let txs = self.inner.lock().await.update(geth_txs).await;
-        let mempool = self.inner.clone();
-        let mempool_ttl = self.mempool_ttl;
-        tokio::spawn(async move {
-            tokio::time::sleep(mempool_ttl).await;
-            mempool.lock().await.remove_txs(txs).await;
-        });
+    #[async_trait]
+    impl TxSender for StubSender {
+        fn transaction_count(&self, _chain_id: u64, address: Address) -> anyhow::Result<u64> {
+            let sent = self.sent.lock().unwrap();
+            Ok(sent.iter().filter(|(sender, _)| *sender == address).count() as u64)
+        }
+
+        fn is_shadow(&self) -> bool {
+            false
+        }
+
+        async fn send_transaction(
+            &self,
+            _chain_id: u64,
+            sender_addr: &str,
+            tx: &QueuedTx,
+        ) -> Result<(), SendError> {
+            let nonce = tx.tx.nonce().map_or(0, |nonce| nonce.as_u64());
+            self.sent
+                .lock()
+                .unwrap()
+                .push((Address::from_str(sender_addr).unwrap(), nonce));
+            Ok(())
+        }
+    }
+
+    async fn mempool(
+        forward_queued: bool,
+        policy: PolicyConfig,
+    ) -> (Arc<Mempool>, Arc<StubSender>) {
+        let retention = StoreRetention {
+            tx_ttl: Duration::from_secs(600),
+            nonce_ttl: Duration::from_secs(3600),
+            dead_letter_ttl: Duration::from_secs(3600),
+        };
+        let (store, stored_state) = MempoolStore::open(CHAIN_ID, None, retention).await.unwrap();
+        let config = MempoolConfig {
+            mempool_ttl: Duration::from_secs(300),
+            forward_queued,
+            shards: 4,
+            dropped_senders_capacity: 16,
+            expiry_capacity: 16,
+            sender: SenderConfig {
+                chain_id: CHAIN_ID,
+                sender_ttl: Duration::from_secs(60),
+                gap_timeout: GAP_TIMEOUT,
+                fee_bump_percent: 10,
+                batch_size: 16,
+                queue_capacity: 16,
+                overflow: OverflowPolicy::Reject,
+                retry: RetryPolicy {
+                    max_retries: 0,
+                    backoff_base: Duration::ZERO,
+                    backoff_cap: Duration::ZERO,
+                    jitter_percent: 0,
+                },
+            },
+        };
+
+        let rome = Arc::new(StubSender::default());
+        let mempool = Mempool::new(
+            rome.clone(),
+            config,
+            Arc::new(store),
+            stored_state,
+            Arc::new(Policy::new(CHAIN_ID, policy)),
+        )
+        .await;
+
+        (mempool, rome)
+    }
+
+    fn sender() -> Address {
+        Address::from_low_u64_be(1)
+    }
+
+    fn transaction(nonce: u64) -> Transaction {
+        let mut tx = Transaction {
+            from: sender(),
+            nonce: nonce.into(),
+            to: Some(Address::zero()),
+            gas: 21_000.into(),
+            gas_price: Some(1_000_000_000u64.into()),
+            value: 1.into(),
+            v: (CHAIN_ID * 2 + 35).into(),
+            r: 1.into(),
+            s: 1.into(),
+            chain_id: Some(CHAIN_ID.into()),
+            ..Default::default()
+        };
+        tx.hash = tx.hash();
+        tx
+    }
+
+    /// Txpool content of the sender in the format of geth `txpool_content`
+    fn txpool(pending: &[u64], queued: &[u64]) -> GethTxPoolResult {
+        let txs = |nonces: &[u64]| {
+            let txs = nonces
+                .iter()
+                .map(|nonce| (nonce.to_string(), json!(transaction(*nonce))))
+                .collect::<serde_json::Map<_, _>>();
+            json!({ to_checksum(&sender(), None): txs })
+        };
+
+        serde_json::from_value(json!({ "pending": txs(pending), "queued": txs(queued) })).unwrap()
+    }
+
+    fn status(mempool: &Mempool, nonce: u64) -> Option<TxStatus> {
+        mempool
+            .tx_status(&transaction(nonce).hash)
+            .map(|tx| tx.status)
+    }
+
+    async fn is_queued(mempool: &Mempool, nonce: u64) -> bool {
+        let (_, shard) = mempool.shard(&to_checksum(&sender(), None));
+        shard.lock().await.queued.contains(&transaction(nonce).hash)
+    }
+
+    /// Wait for the sender queue to forward the transactions
+    async fn wait_sent(rome: &StubSender, nonces: &[u64]) {
+        for _ in 0..500 {
+            if rome.sent() == nonces {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(rome.sent(), nonces);
+    }
+
+    #[tokio::test]
+    async fn queued_tx_is_forwarded_once_pending() {
+        let (mempool, rome) = mempool(false, PolicyConfig::default()).await;
+
+        mempool.update(&txpool(&[], &[1])).await;
+        assert!(is_queued(&mempool, 1).await);
+        assert_eq!(status(&mempool, 1), None);
+
+        mempool.update(&txpool(&[0], &[1])).await;
+        wait_sent(&rome, &[0]).await;
+        assert!(is_queued(&mempool, 1).await);
+        assert_eq!(status(&mempool, 1), None);
+
+        mempool.update(&txpool(&[0, 1], &[])).await;
+        wait_sent(&rome, &[0, 1]).await;
+        assert!(!is_queued(&mempool, 1).await);
+        assert_eq!(status(&mempool, 1), Some(TxStatus::Forwarded));
+    }
+
+    #[tokio::test]
+    async fn queued_tx_is_forwarded_eagerly() {
+        let (mempool, rome) = mempool(true, PolicyConfig::default()).await;
+
+        // The sender queue holds the transaction until the missing nonce arrives
+        mempool.update(&txpool(&[], &[1])).await;
+        assert!(is_queued(&mempool, 1).await);
+        assert_eq!(status(&mempool, 1), Some(TxStatus::Pending));
+        tokio::time::sleep(GAP_TIMEOUT / 4).await;
+        assert!(rome.sent().is_empty());
+
+        mempool.update(&txpool(&[0, 1], &[])).await;
+        wait_sent(&rome, &[0, 1]).await;
+        assert!(!is_queued(&mempool, 1).await);
+        assert_eq!(status(&mempool, 1), Some(TxStatus::Forwarded));
+    }
+
+    #[tokio::test]
+    async fn queued_tx_failed_on_nonce_gap_is_forwarded_once_pending() {
+        let (mempool, rome) = mempool(true, PolicyConfig::default()).await;
+
+        mempool.update(&txpool(&[], &[1])).await;
+        for _ in 0..500 {
+            if status(&mempool, 1) == Some(TxStatus::Failed) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(status(&mempool, 1), Some(TxStatus::Failed));
+
+        mempool.update(&txpool(&[0, 1], &[])).await;
+        wait_sent(&rome, &[0, 1]).await;
+        assert_eq!(status(&mempool, 1), Some(TxStatus::Forwarded));
+    }
+
+    #[tokio::test]
+    async fn rejected_tx_is_checked_again() {
+        let policy = PolicyConfig {
+            min_gas_price: Some(2_000_000_000),
+            ..Default::default()
+        };
+        let (mempool, rome) = mempool(false, policy).await;
+
+        mempool.update(&txpool(&[0], &[])).await;
+        assert_eq!(status(&mempool, 0), Some(TxStatus::Rejected));
+
+        // The successor is not held back by the transaction known as rejected
+        mempool.ctx.policy.update(PolicyConfig::default());
+        mempool.update(&txpool(&[0, 1], &[])).await;
+        wait_sent(&rome, &[0, 1]).await;
+        assert_eq!(status(&mempool, 0), Some(TxStatus::Forwarded));
+        assert_eq!(status(&mempool, 1), Some(TxStatus::Forwarded));
     }
 }
@@ -1,172 +1,624 @@
-use crate::rome_sender::RomeSender;
+use crate::metrics::{
+    CHANNEL_OCCUPANCY, DEAD_LETTERS, FORWARD_LATENCY, QUEUE_DEPTH, REPLACEMENTS, RETRIES,
+    TXS_EMULATED, TXS_FAILED, TXS_FORWARDED,
+};
+use crate::rome_sender::TxSender;
+use crate::store::{unix_timestamp, DeadLetter, MempoolStore};
 use ethers::prelude::transaction::eip2718::TypedTransaction;
 use ethers::prelude::Signature;
-use rome_sdk::rome_evm_client::error::ProgramResult;
-use rome_sdk::rome_evm_client::error::RomeEvmError::Custom;
+use ethers::types::{Address, Bytes, TxHash, U256};
+use ethers::utils::rlp::Rlp;
+use rand::Rng;
 use rome_sdk::rome_geth::types::GethTxPoolTx;
-use rome_sdk::{EthSignedTxTuple, RheaTx};
 use std::collections::BTreeMap;
-use std::sync::Arc;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::mpsc;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+
+/// Settings shared by all sender queues
+#[derive(Debug, Clone)]
+pub struct SenderConfig {
+    pub chain_id: u64,
+    /// Sender queue is dropped if no transactions are received during this time
+    pub sender_ttl: Duration,
+    /// Transactions waiting for a missing predecessor nonce are dropped after this time
+    pub gap_timeout: Duration,
+    /// Minimal fee increase in percents for a transaction to replace the queued one
+    pub fee_bump_percent: u64,
+    /// Maximal number of events received from the queue at once
+    pub batch_size: usize,
+    /// Maximal number of buffered events and of queued transactions of the sender
+    pub queue_capacity: usize,
+    pub overflow: OverflowPolicy,
+    pub retry: RetryPolicy,
+}
+
+/// Handling of a transaction for the full sender queue
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Reject the transaction, it is received from the txpool again after it expires
+    #[default]
+    Reject,
+    /// Drop the sender queue with all its transactions
+    DropSender,
+    /// Wait for the queue, ingestion of new transactions pauses meanwhile
+    Pause,
+}
+
+impl OverflowPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverflowPolicy::Reject => "reject",
+            OverflowPolicy::DropSender => "drop_sender",
+            OverflowPolicy::Pause => "pause",
+        }
+    }
+}
+
+/// Retries of transient send errors with exponential backoff
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for every next one
+    pub backoff_base: Duration,
+    /// Maximal delay between retries
+    pub backoff_cap: Duration,
+    /// Maximal random addition to the delay in percents of the delay
+    pub jitter_percent: u64,
+}
+
+impl RetryPolicy {
+    /// Delay before the retry, starting from 1
+    fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .backoff_base
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.backoff_cap);
+        let jitter = rand::thread_rng().gen_range(0..=self.jitter_percent);
+
+        delay + delay.mul_f64(jitter as f64 / 100.0)
+    }
+}
+
+/// Signed transaction in the sender queue
+#[derive(Debug, Clone)]
+pub struct QueuedTx {
+    pub hash: TxHash,
+    pub tx: TypedTransaction,
+    pub signature: Signature,
+    /// Unix timestamp of receiving the transaction
+    pub received_at: u64,
+    /// Time the transaction is first seen by this process
+    pub seen: Instant,
+}
+
+impl QueuedTx {
+    pub fn from_geth(geth_tx: &GethTxPoolTx) -> anyhow::Result<Self> {
+        let tx = TypedTransaction::try_from(geth_tx).map_err(|err| {
+            anyhow::anyhow!("Failed to convert pool tx into TypedTransaction: {:?}", err)
+        })?;
+
+        Ok(Self {
+            hash: geth_tx.hash,
+            tx,
+            signature: Signature {
+                r: geth_tx.r,
+                s: geth_tx.s,
+                v: geth_tx.v.as_u64(),
+            },
+            received_at: unix_timestamp(),
+            seen: Instant::now(),
+        })
+    }
+
+    /// Decode the signed RLP-encoded transaction
+    pub fn from_raw(raw_tx: &Bytes) -> anyhow::Result<Self> {
+        let (tx, signature) = TypedTransaction::decode_signed(&Rlp::new(raw_tx))?;
+
+        Ok(Self {
+            hash: tx.hash(&signature),
+            tx,
+            signature,
+            received_at: unix_timestamp(),
+            seen: Instant::now(),
+        })
+    }
+
+    /// Signed RLP-encoded transaction
+    pub fn raw(&self) -> Bytes {
+        self.tx.rlp_signed(&self.signature)
+    }
+}
+
+/// Transaction failed to be forwarded with all retries
+#[derive(Debug)]
+struct SendFailure {
+    /// Last error of [TxSender::send_transaction]
+    error: String,
+    attempts: u32,
+}
+
+/// Message to the sender queue
+#[derive(Debug)]
+pub enum SenderEvent {
+    /// Transaction with the nonce
+    Tx(u64, Box<QueuedTx>),
+    /// Drop the queued transaction
+    DropTx(TxHash),
+    /// Pause or resume forwarding
+    Pause(bool),
+}
+
+/// State of the sender queue exposed to the admin API
+#[derive(serde::Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderInfo {
+    /// Transactions waiting to be forwarded
+    pub queue_depth: usize,
+    /// Nonce of the last forwarded transaction
+    pub last_nonce: Option<u64>,
+    /// Next nonce expected by Rome-EVM
+    pub next_nonce: Option<u64>,
+    pub paused: bool,
+}
+
+pub struct SenderHandle {
+    pub events: mpsc::Sender<SenderEvent>,
+    pub info: Arc<Mutex<SenderInfo>>,
+}
 
 pub struct MempoolSender {
     sender_addr: String,
-    tx_recv: UnboundedReceiver<(u64, GethTxPoolTx)>,
-    rome: Arc<RomeSender>,
+    tx_recv: mpsc::Receiver<SenderEvent>,
+    rome: Arc<dyn TxSender>,
+    store: Arc<MempoolStore>,
+    config: SenderConfig,
+    info: Arc<Mutex<SenderInfo>>,
+    paused: bool,
 }
 
 impl MempoolSender {
     pub fn init(
         sender_addr: String,
-        rome: Arc<RomeSender>,
-        sender_ttl: Duration,
-        drop_sender_tx: UnboundedSender<String>,
-    ) -> UnboundedSender<(u64, GethTxPoolTx)> {
-        let (tx_send, tx_recv) = mpsc::unbounded_channel();
+        rome: Arc<dyn TxSender>,
+        config: SenderConfig,
+        drop_sender_tx: mpsc::Sender<String>,
+        store: Arc<MempoolStore>,
+        paused: bool,
+    ) -> SenderHandle {
+        let (tx_send, tx_recv) = mpsc::channel(config.queue_capacity);
+        let info = Arc::new(Mutex::new(SenderInfo {
+            paused,
+            ..Default::default()
+        }));
         tokio::spawn(
             Self {
                 sender_addr,
                 tx_recv,
                 rome,
+                store,
+                config,
+                info: info.clone(),
+                paused,
             }
-            .sender_task(sender_ttl, drop_sender_tx),
+            .sender_task(drop_sender_tx),
         );
 
-        tx_send
+        SenderHandle {
+            events: tx_send,
+            info,
+        }
     }
 
-    async fn sender_task(mut self, sender_ttl: Duration, drop_sender_tx: UnboundedSender<String>) {
-        const BATCH_SIZE: usize = 100;
-        const NUM_SEND_TX_RETRIES: usize = 5;
-        let mut txs = BTreeMap::new();
-        let mut last_processed_nonce = None;
+    /// Next nonce of the sender expected by Rome-EVM.
+    /// Falls back to the last forwarded nonce if Rome-EVM is not available.
+    async fn expected_nonce(&self, fallback: Option<u64>) -> Option<u64> {
+        let rome = self.rome.clone();
+        let chain_id = self.config.chain_id;
+        let sender_addr = self.sender_addr.clone();
+        // Rome-EVM client reads the state with blocking calls
+        let onchain = tokio::task::spawn_blocking(move || -> anyhow::Result<u64> {
+            let address = Address::from_str(&sender_addr)?;
+            rome.transaction_count(chain_id, address)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
 
-        loop {
-            // Try to receive all recent transactions from a queue
-            let mut new_events = Vec::with_capacity(BATCH_SIZE);
-            let Ok(num_received) = tokio::time::timeout(
-                sender_ttl,
-                self.tx_recv.recv_many(&mut new_events, BATCH_SIZE),
-            )
-            .await
-            else {
-                // Timeout
-                if let Err(err) = drop_sender_tx.send(self.sender_addr.clone()) {
-                    tracing::warn!("Failed to send sender address to drop_sender_tx: {:?}", err);
-                }
-                return;
-            };
+        match onchain {
+            Ok(nonce) => Some(nonce),
+            Err(err) => {
+                tracing::warn!(
+                    "SenderQueue {}: Failed to get nonce from Rome-EVM: {:?}",
+                    self.sender_addr,
+                    err
+                );
+                fallback
+            }
+        }
+    }
 
-            if num_received == 0 {
-                // Sender part of a channel is dropped
-                if let Err(err) = drop_sender_tx.send(self.sender_addr.clone()) {
-                    tracing::warn!("Failed to send sender address to drop_sender_tx: {:?}", err);
-                }
+    /// Drop transactions with nonces already used in Rome-EVM
+    async fn skip_processed(&self, txs: &mut BTreeMap<u64, QueuedTx>, expected_nonce: Option<u64>) {
+        let Some(expected_nonce) = expected_nonce else {
+            return;
+        };
+
+        let pending = txs.split_off(&expected_nonce);
+        for (nonce, tx) in std::mem::replace(txs, pending) {
+            tracing::warn!(
+                "SenderQueue {}: Transaction {:?} arrived after nonce {} was already used",
+                self.sender_addr,
+                tx.hash,
+                nonce
+            );
+            REPLACEMENTS
+                .with_label_values(&[&self.config.chain_id.to_string(), "nonce_used"])
+                .inc();
+            self.fail_tx(tx.hash, "nonce_used").await;
+        }
+    }
+
+    /// Queue the transaction. A queued transaction with the same nonce is replaced
+    /// only if the fee is bumped at least by `fee_bump_percent`, like in geth.
+    async fn queue_tx(&self, txs: &mut BTreeMap<u64, QueuedTx>, nonce: u64, tx: QueuedTx) {
+        let Some(queued) = txs.get(&nonce) else {
+            if txs.len() >= self.config.queue_capacity {
+                tracing::warn!(
+                    "SenderQueue {}: Queue is full, transaction {:?} is rejected",
+                    self.sender_addr,
+                    tx.hash
+                );
+                self.fail_tx(tx.hash, "queue_full").await;
                 return;
             }
 
-            // Order transactions by nonce
-            for (nonce, tx) in new_events {
-                txs.insert(nonce, tx);
-            }
+            txs.insert(nonce, tx);
+            return;
+        };
 
-            // Send transactions
-            while let Some((nonce, geth_tx)) = txs.pop_first() {
-                if let Some(last_processed_nonce) = last_processed_nonce {
-                    if nonce <= last_processed_nonce {
-                        tracing::warn!("SenderQueue {}: Skipping transaction with nonce {} as it is already processed", self.sender_addr, nonce);
-                        continue;
+        if is_fee_bumped(queued, &tx, self.config.fee_bump_percent) {
+            tracing::info!(
+                "SenderQueue {}: Transaction {:?} with nonce {} replaced by {:?}",
+                self.sender_addr,
+                queued.hash,
+                nonce,
+                tx.hash
+            );
+            REPLACEMENTS
+                .with_label_values(&[&self.config.chain_id.to_string(), "replaced"])
+                .inc();
+            self.store.tx_replaced(queued.hash).await;
+            txs.insert(nonce, tx);
+        } else {
+            tracing::warn!(
+                "SenderQueue {}: Replacement {:?} of transaction {:?} with nonce {} is underpriced",
+                self.sender_addr,
+                tx.hash,
+                queued.hash,
+                nonce
+            );
+            REPLACEMENTS
+                .with_label_values(&[&self.config.chain_id.to_string(), "underpriced"])
+                .inc();
+            self.fail_tx(tx.hash, "underpriced").await;
+        }
+    }
+
+    async fn sender_task(mut self, drop_sender_tx: mpsc::Sender<String>) {
+        let mut txs = BTreeMap::new();
+        let mut last_nonce = self.store.last_nonce(&self.sender_addr);
+        let mut expected_nonce = self.expected_nonce(last_nonce.map(|n| n + 1)).await;
+        // Deadline of waiting for the missing nonce
+        let mut gap_deadline: Option<Instant> = None;
+        let mut waiting_nonce = expected_nonce;
+
+        loop {
+            self.update_info(&txs, last_nonce, expected_nonce);
+
+            // Try to receive all recent transactions from a queue.
+            // Paused queue keeps its transactions until it is resumed.
+            let wait = match gap_deadline {
+                _ if self.paused && !txs.is_empty() => None,
+                Some(deadline) => Some(
+                    self.config
+                        .sender_ttl
+                        .min(deadline.saturating_duration_since(Instant::now())),
+                ),
+                None => Some(self.config.sender_ttl),
+            };
+            let mut new_events = Vec::with_capacity(self.config.batch_size);
+            let received = self
+                .tx_recv
+                .recv_many(&mut new_events, self.config.batch_size);
+            let received = match wait {
+                Some(wait) => tokio::time::timeout(wait, received).await,
+                None => Ok(received.await),
+            };
+            match received {
+                Ok(0) => {
+                    // Sender part of a channel is dropped
+                    for (_, tx) in std::mem::take(&mut txs) {
+                        self.drop_tx(tx.hash).await;
                     }
+                    self.exit(&drop_sender_tx);
+                    return;
                 }
-
-                let tx_hash = geth_tx.hash;
-                if let Err(err) = self
-                    .send_tx_with_retries(geth_tx, NUM_SEND_TX_RETRIES)
-                    .await
-                {
-                    // Unable to send a transaction with retries - drop mempool sender
-                    if let Err(err) = drop_sender_tx.send(self.sender_addr.clone()) {
+                Ok(received) => {
+                    CHANNEL_OCCUPANCY
+                        .with_label_values(&[&self.config.chain_id.to_string(), "senders"])
+                        .sub(received as i64);
+                    for event in new_events {
+                        match event {
+                            // Order transactions by nonce
+                            SenderEvent::Tx(nonce, tx) => self.queue_tx(&mut txs, nonce, *tx).await,
+                            SenderEvent::DropTx(hash) => {
+                                let queue_depth = txs.len();
+                                txs.retain(|_, tx| tx.hash != hash);
+                                if txs.len() < queue_depth {
+                                    self.drop_tx(hash).await;
+                                }
+                            }
+                            SenderEvent::Pause(paused) => {
+                                tracing::info!(
+                                    "SenderQueue {}: paused: {}",
+                                    self.sender_addr,
+                                    paused
+                                );
+                                self.paused = paused;
+                            }
+                        }
+                    }
+                }
+                Err(_) if gap_deadline.is_some_and(|deadline| deadline <= Instant::now()) => {
+                    // The missing nonce may be used by a transaction sent bypassing Rhea
+                    expected_nonce = self.expected_nonce(expected_nonce).await;
+                    self.skip_processed(&mut txs, expected_nonce).await;
+                    if txs
+                        .first_key_value()
+                        .is_some_and(|(nonce, _)| Some(*nonce) != expected_nonce)
+                    {
                         tracing::warn!(
-                            "Failed to send sender address to drop_sender_tx: {:?}",
-                            err
+                            "SenderQueue {}: Nonce {:?} is missing for {:?}, dropping {} transactions",
+                            self.sender_addr,
+                            expected_nonce,
+                            self.config.gap_timeout,
+                            txs.len()
                         );
+                        for (_, tx) in std::mem::take(&mut txs) {
+                            self.fail_tx(tx.hash, "nonce_gap").await;
+                        }
                     }
-
-                    tracing::warn!(
-                        "SenderQueue {}: Failed to send transaction {}: {:?}",
-                        self.sender_addr,
-                        tx_hash,
-                        err
-                    );
+                    gap_deadline = None;
+                }
+                Err(_) => {
+                    // Timeout, transactions received meanwhile are dropped
+                    self.tx_recv.close();
+                    while let Ok(event) = self.tx_recv.try_recv() {
+                        CHANNEL_OCCUPANCY
+                            .with_label_values(&[&self.config.chain_id.to_string(), "senders"])
+                            .dec();
+                        if let SenderEvent::Tx(_, tx) = event {
+                            self.drop_tx(tx.hash).await;
+                        }
+                    }
+                    self.exit(&drop_sender_tx);
                     return;
                 }
+            }
+
+            self.skip_processed(&mut txs, expected_nonce).await;
 
-                last_processed_nonce = Some(nonce);
+            // Send transactions in nonce order while there are no gaps
+            while let Some(entry) = txs.first_entry() {
+                let nonce = *entry.key();
+                if self.paused
+                    || expected_nonce.is_some_and(|expected_nonce| nonce != expected_nonce)
+                {
+                    break;
+                }
+
+                let tx = entry.remove();
+                let tx_hash = tx.hash;
+                match self.send_tx_with_retries(&tx).await {
+                    // Nothing is sent in the shadow mode, the queue moves on whatever the result is
+                    result if self.rome.is_shadow() => {
+                        self.tx_emulated(tx_hash, result.is_ok()).await
+                    }
+                    Err(failure) => {
+                        tracing::error!(
+                            "SenderQueue {}: Transaction {:?} is dead-lettered after {} attempts: {}",
+                            self.sender_addr,
+                            tx_hash,
+                            failure.attempts,
+                            failure.error
+                        );
+                        DEAD_LETTERS
+                            .with_label_values(&[&self.config.chain_id.to_string()])
+                            .inc();
+                        TXS_FAILED
+                            .with_label_values(&[&self.config.chain_id.to_string(), "send_failed"])
+                            .inc();
+                        self.store
+                            .tx_dead_lettered(
+                                tx_hash,
+                                DeadLetter {
+                                    sender: self.sender_addr.clone(),
+                                    nonce,
+                                    raw_tx: tx.raw(),
+                                    error: failure.error,
+                                    attempts: failure.attempts,
+                                    first_seen: tx.received_at,
+                                    failed_at: unix_timestamp(),
+                                },
+                            )
+                            .await;
+
+                        // The nonce may have been used anyway, continue from the nonce of Rome-EVM
+                        expected_nonce = self.expected_nonce(expected_nonce).await;
+                        self.skip_processed(&mut txs, expected_nonce).await;
+                        continue;
+                    }
+                    Ok(()) => {
+                        self.store
+                            .tx_forwarded(tx_hash, &self.sender_addr, nonce)
+                            .await;
+                        TXS_FORWARDED
+                            .with_label_values(&[&self.config.chain_id.to_string()])
+                            .inc();
+                        FORWARD_LATENCY
+                            .with_label_values(&[&self.config.chain_id.to_string()])
+                            .observe(tx.seen.elapsed().as_secs_f64());
+                    }
+                }
+
+                last_nonce = Some(nonce);
+                expected_nonce = Some(nonce + 1);
+                self.update_info(&txs, last_nonce, expected_nonce);
+            }
+
+            // Wait for the missing nonce, the deadline restarts once the queue moves on
+            if txs.is_empty() || self.paused || expected_nonce != waiting_nonce {
+                gap_deadline = None;
             }
+            if !txs.is_empty() && !self.paused && gap_deadline.is_none() {
+                tracing::info!(
+                    "SenderQueue {}: Waiting for nonce {:?}",
+                    self.sender_addr,
+                    expected_nonce
+                );
+                gap_deadline = Some(Instant::now() + self.config.gap_timeout);
+            }
+            waiting_nonce = expected_nonce;
         }
     }
 
+    /// Ask the mempool to forget the sender queue
+    fn exit(&self, drop_sender_tx: &mpsc::Sender<String>) {
+        QUEUE_DEPTH
+            .remove_label_values(&[&self.config.chain_id.to_string(), &self.sender_addr])
+            .ok();
+        // The mempool also forgets the queue once it fails to pass a transaction to it
+        if let Err(err) = drop_sender_tx.try_send(self.sender_addr.clone()) {
+            tracing::warn!("Failed to send sender address to drop_sender_tx: {:?}", err);
+        }
+    }
+
+    async fn tx_emulated(&self, hash: TxHash, success: bool) {
+        TXS_EMULATED
+            .with_label_values(&[
+                &self.config.chain_id.to_string(),
+                if success { "success" } else { "failure" },
+            ])
+            .inc();
+        if success {
+            self.store.tx_emulated(hash).await;
+        } else {
+            self.store.tx_failed(hash).await;
+        }
+    }
+
+    async fn fail_tx(&self, hash: TxHash, reason: &'static str) {
+        TXS_FAILED
+            .with_label_values(&[&self.config.chain_id.to_string(), reason])
+            .inc();
+        self.store.tx_failed(hash).await;
+    }
+
+    async fn drop_tx(&self, hash: TxHash) {
+        TXS_FAILED
+            .with_label_values(&[&self.config.chain_id.to_string(), "dropped"])
+            .inc();
+        self.store.tx_dropped(hash).await;
+    }
+
+    fn update_info(
+        &self,
+        txs: &BTreeMap<u64, QueuedTx>,
+        last_nonce: Option<u64>,
+        next_nonce: Option<u64>,
+    ) {
+        QUEUE_DEPTH
+            .with_label_values(&[&self.config.chain_id.to_string(), &self.sender_addr])
+            .set(txs.len() as i64);
+        *self.info.lock().expect("sender info lock poisoned") = SenderInfo {
+            queue_depth: txs.len(),
+            last_nonce,
+            next_nonce,
+            paused: self.paused,
+        };
+    }
+
     #[tracing::instrument(
         name = "rhea::send_tx_with_retries",
-        skip(self),
-        fields(tx_hash = ?geth_tx.hash)
+        skip(self, tx),
+        fields(tx_hash = ?tx.hash)
     )]
-    async fn send_tx_with_retries(
-        &self,
-        geth_tx: GethTxPoolTx,
-        num_retries: usize,
-    ) -> ProgramResult<()> {
-        let mut retries_left = num_retries;
-        let signature = Signature {
-            r: geth_tx.r,
-            s: geth_tx.s,
-            v: geth_tx.v.as_u64(),
-        };
+    async fn send_tx_with_retries(&self, tx: &QueuedTx) -> Result<(), SendFailure> {
+        let retry = &self.config.retry;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let Err(err) = self
+                .rome
+                .send_transaction(self.config.chain_id, &self.sender_addr, tx)
+                .await
+            else {
+                break Ok(());
+            };
 
-        match TypedTransaction::try_from(&geth_tx) {
-            Ok(tx) => {
-                let mut retry_delay = 2;
-                loop {
-                    let rhea_tx = RheaTx::new(EthSignedTxTuple::new(tx.clone(), signature));
-                    match self
-                        .rome
-                        .send_transaction(&geth_tx.hash, &self.sender_addr, rhea_tx)
-                        .await
-                    {
-                        Ok(_) => break Ok(()),
-                        Err(_) => {
-                            tracing::warn!(
-                                "SenderQueue {}: Failed to send transaction {:?}",
-                                self.sender_addr,
-                                geth_tx.hash,
-                            );
-                        }
-                    }
+            let transient = err.is_transient();
+            tracing::warn!(
+                "SenderQueue {}: Failed to send transaction {:?} (transient: {}): {}",
+                self.sender_addr,
+                tx.hash,
+                transient,
+                err
+            );
 
-                    if retries_left > 0 {
-                        retries_left -= 1;
-                        tracing::info!(
-                            "SenderQueue {}: Will retry {:?} in {:?} seconds",
-                            self.sender_addr,
-                            geth_tx.hash,
-                            retry_delay
-                        );
-                        tokio::time::sleep(Duration::from_secs(retry_delay)).await;
-                        retry_delay *= 2;
-                    } else {
-                        break Err(Custom(format!(
-                            "SenderQueue {}: No retries left for {:?}",
-                            self.sender_addr, geth_tx.hash
-                        )));
-                    }
-                }
+            // One report record per transaction in the shadow mode
+            if transient && attempts <= retry.max_retries && !self.rome.is_shadow() {
+                let delay = retry.delay(attempts);
+                RETRIES
+                    .with_label_values(&[&self.config.chain_id.to_string()])
+                    .inc();
+                tracing::info!(
+                    "SenderQueue {}: Will retry {:?} in {:?}",
+                    self.sender_addr,
+                    tx.hash,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+            } else {
+                break Err(SendFailure {
+                    error: err.to_string(),
+                    attempts,
+                });
             }
-            Err(err) => Err(Custom(format!(
-                "SenderQueue {}: Failed to convert pool tx into TypedTransaction: {:?}",
-                self.sender_addr, err
-            ))),
         }
     }
 }
+
+/// Fee cap and priority fee of the transaction
+pub fn fees(tx: &QueuedTx) -> Option<(U256, U256)> {
+    match &tx.tx {
+        TypedTransaction::Eip1559(tx) => Some((tx.max_fee_per_gas?, tx.max_priority_fee_per_gas?)),
+        tx => tx.gas_price().map(|gas_price| (gas_price, gas_price)),
+    }
+}
+
+/// Both fee cap and priority fee of the replacement are higher by `percent`
+fn is_fee_bumped(queued: &QueuedTx, replacement: &QueuedTx, percent: u64) -> bool {
+    let (Some((queued_cap, queued_tip)), Some((cap, tip))) = (fees(queued), fees(replacement))
+    else {
+        return false;
+    };
+
+    let bumped = |old: U256, new: U256| {
+        new.saturating_mul(100.into()) >= old.saturating_mul((100 + percent).into())
+    };
+    bumped(queued_cap, cap) && bumped(queued_tip, tip)
+}
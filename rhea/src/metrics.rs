@@ -0,0 +1,194 @@
+use jsonrpsee::core::BoxError;
+use jsonrpsee::server::{serve, HttpBody, HttpRequest, HttpResponse};
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge_vec, HistogramVec,
+    IntCounterVec, IntGaugeVec, TextEncoder,
+};
+use std::future::Ready;
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::task::{Context, Poll};
+use tokio::net::TcpListener;
+
+pub static TXS_RECEIVED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_txs_received_total",
+        "Number of new transactions received from the geth txpool",
+        &["chain_id"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static TXS_FORWARDED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_txs_forwarded_total",
+        "Number of transactions executed in Rome-EVM",
+        &["chain_id"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static TXS_EMULATED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_txs_emulated_total",
+        "Number of transactions composed and emulated in the shadow mode by the result",
+        &["chain_id", "result"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static TXS_FAILED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_txs_failed_total",
+        "Number of transactions not forwarded by the reason",
+        &["chain_id", "reason"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static RETRIES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_send_retries_total",
+        "Number of retries of sending transactions",
+        &["chain_id"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static FORWARD_LATENCY: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "rhea_forward_latency_seconds",
+        "Time from receiving the transaction from the txpool to its execution in Rome-EVM",
+        &["chain_id"],
+        vec![0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0]
+    )
+    .expect("metric can be registered")
+});
+
+pub static ACTIVE_SENDERS: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "rhea_active_senders",
+        "Number of active sender queues",
+        &["chain_id"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static QUEUE_DEPTH: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "rhea_sender_queue_depth",
+        "Number of transactions waiting to be forwarded in the sender queue",
+        &["chain_id", "sender"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static RPC_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_rpc_submissions_total",
+        "Number of transactions submitted through the Solana RPC by the result",
+        &["chain_id", "url", "result"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static REPLACEMENTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_replacements_total",
+        "Number of transactions received for the nonce of another transaction of the sender",
+        &["chain_id", "result"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static DEAD_LETTERS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_dead_letters_total",
+        "Number of transactions failed to be forwarded with all retries",
+        &["chain_id"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static TXS_REJECTED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_txs_rejected_total",
+        "Number of transactions rejected by the admission policy by the reason",
+        &["chain_id", "reason"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static CHANNEL_OCCUPANCY: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register_int_gauge_vec!(
+        "rhea_channel_occupancy",
+        "Number of entries waiting in the internal channels",
+        &["chain_id", "channel"]
+    )
+    .expect("metric can be registered")
+});
+
+pub static CHANNEL_OVERFLOWS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "rhea_channel_overflows_total",
+        "Number of entries handled by the overflow policy of the full channel",
+        &["chain_id", "channel", "policy"]
+    )
+    .expect("metric can be registered")
+});
+
+/// Render all metrics in the Prometheus text format
+pub fn render() -> String {
+    TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .unwrap_or_else(|err| {
+            tracing::warn!("Failed to encode metrics: {:?}", err);
+            String::new()
+        })
+}
+
+/// HTTP service serving /metrics
+#[derive(Clone)]
+struct MetricsService;
+
+impl<B> tower::Service<HttpRequest<B>> for MetricsService {
+    type Response = HttpResponse;
+    type Error = BoxError;
+    type Future = Ready<Result<HttpResponse, BoxError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: HttpRequest<B>) -> Self::Future {
+        let (status, body) = match request.uri().path() {
+            "/metrics" => (200, render()),
+            _ => (404, "Not found".to_string()),
+        };
+
+        std::future::ready(Ok(HttpResponse::builder()
+            .status(status)
+            .header("content-type", "text/plain; charset=utf-8")
+            .body(HttpBody::from(body))
+            .expect("Unable to build the response")))
+    }
+}
+
+/// Start the HTTP server serving /metrics
+pub async fn start_http_server(host: SocketAddr) -> anyhow::Result<()> {
+    tracing::info!("Starting the metrics server at {host}");
+
+    let listener = TcpListener::bind(host).await?;
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, MetricsService));
+                }
+                Err(err) => tracing::error!("Failed to accept the connection: {:?}", err),
+            }
+        }
+    });
+
+    Ok(())
+}
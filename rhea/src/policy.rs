@@ -0,0 +1,463 @@
+use crate::config::RheaConfig;
+use crate::mempool_sender::{fees, QueuedTx};
+use crate::metrics::TXS_REJECTED;
+use ethers::types::{Address, TxHash, U256};
+use rome_sdk::rome_utils::config::ReadableConfig;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+const DEFAULT_MAX_SENDERS: usize = 100_000;
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct SenderQuota {
+    /// Number of transactions of the sender admitted every second
+    pub txs_per_sec: u32,
+    /// Maximum number of transactions admitted at once, `txs_per_sec` by default
+    pub burst: Option<u32>,
+}
+
+/// Transactions forwarded to Rome-EVM, everything is admitted by default
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct PolicyConfig {
+    /// Only transactions of these senders are admitted if set
+    pub allowed_senders: Option<HashSet<Address>>,
+    #[serde(default)]
+    pub denied_senders: HashSet<Address>,
+    /// Only transactions to these addresses are admitted if set, contract deployments are rejected
+    pub allowed_recipients: Option<HashSet<Address>>,
+    #[serde(default)]
+    pub denied_recipients: HashSet<Address>,
+    /// Minimal gas price of legacy transactions and fee cap of EIP-1559 transactions in wei
+    pub min_gas_price: Option<u64>,
+    /// Minimal priority fee in wei
+    pub min_priority_fee: Option<u64>,
+    pub max_gas_limit: Option<u64>,
+    /// Maximal size of the transaction data in bytes
+    pub max_calldata_size: Option<usize>,
+    /// Rate of transactions of every sender, not limited if not set
+    pub sender_rate_limit: Option<SenderQuota>,
+}
+
+/// Reason of rejecting the transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Rejection {
+    #[error("sender is not allowed")]
+    SenderNotAllowed,
+    #[error("sender is denied")]
+    SenderDenied,
+    #[error("recipient is not allowed")]
+    RecipientNotAllowed,
+    #[error("recipient is denied")]
+    RecipientDenied,
+    #[error("gas price is below the minimum")]
+    GasPrice,
+    #[error("priority fee is below the minimum")]
+    PriorityFee,
+    #[error("gas limit is above the maximum")]
+    GasLimit,
+    #[error("calldata is above the maximal size")]
+    CalldataSize,
+    #[error("sender is rate limited")]
+    RateLimited,
+}
+
+impl Rejection {
+    /// Label of the rejection metric
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rejection::SenderNotAllowed => "sender_not_allowed",
+            Rejection::SenderDenied => "sender_denied",
+            Rejection::RecipientNotAllowed => "recipient_not_allowed",
+            Rejection::RecipientDenied => "recipient_denied",
+            Rejection::GasPrice => "gas_price",
+            Rejection::PriorityFee => "priority_fee",
+            Rejection::GasLimit => "gas_limit",
+            Rejection::CalldataSize => "calldata_size",
+            Rejection::RateLimited => "rate_limited",
+        }
+    }
+}
+
+struct Bucket {
+    txs: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, quota: &SenderQuota) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.txs = (self.txs + elapsed * quota.txs_per_sec as f64).min(burst(quota));
+        self.updated = now;
+    }
+}
+
+fn burst(quota: &SenderQuota) -> f64 {
+    quota.burst.unwrap_or(quota.txs_per_sec) as f64
+}
+
+/// Admission policy applied to transactions before they are passed to the sender queues
+pub struct Policy {
+    chain_id: u64,
+    config: RwLock<PolicyConfig>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl Policy {
+    pub fn new(chain_id: u64, config: PolicyConfig) -> Self {
+        Self {
+            chain_id,
+            config: RwLock::new(config),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replace the policy, rate limits of senders are kept
+    pub fn update(&self, config: PolicyConfig) {
+        *self.config.write().expect("policy lock poisoned") = config;
+    }
+
+    /// Check the transaction, rejections are logged and counted
+    pub fn admit(&self, hash: TxHash, sender: &str, tx: &QueuedTx) -> Result<(), Rejection> {
+        let result = self.check(sender, tx);
+        if let Err(rejection) = result {
+            tracing::warn!(
+                "Transaction {:?} of {} rejected: {}",
+                hash,
+                sender,
+                rejection
+            );
+            TXS_REJECTED
+                .with_label_values(&[&self.chain_id.to_string(), rejection.as_str()])
+                .inc();
+        }
+
+        result
+    }
+
+    /// Check the transaction without logging and counting the rejection
+    pub fn check(&self, sender: &str, tx: &QueuedTx) -> Result<(), Rejection> {
+        let config = self.config.read().expect("policy lock poisoned");
+
+        let sender_address = Address::from_str(sender).ok();
+        if let Some(allowed) = &config.allowed_senders {
+            if !sender_address.is_some_and(|address| allowed.contains(&address)) {
+                return Err(Rejection::SenderNotAllowed);
+            }
+        }
+        if sender_address.is_some_and(|address| config.denied_senders.contains(&address)) {
+            return Err(Rejection::SenderDenied);
+        }
+
+        let recipient = tx.tx.to_addr().copied();
+        if let Some(allowed) = &config.allowed_recipients {
+            if !recipient.is_some_and(|address| allowed.contains(&address)) {
+                return Err(Rejection::RecipientNotAllowed);
+            }
+        }
+        if recipient.is_some_and(|address| config.denied_recipients.contains(&address)) {
+            return Err(Rejection::RecipientDenied);
+        }
+
+        let (fee_cap, priority_fee) = fees(tx).unwrap_or_default();
+        if config
+            .min_gas_price
+            .is_some_and(|min| fee_cap < U256::from(min))
+        {
+            return Err(Rejection::GasPrice);
+        }
+        if config
+            .min_priority_fee
+            .is_some_and(|min| priority_fee < U256::from(min))
+        {
+            return Err(Rejection::PriorityFee);
+        }
+
+        let gas_limit = tx.tx.gas().copied().unwrap_or_default();
+        if config
+            .max_gas_limit
+            .is_some_and(|max| gas_limit > U256::from(max))
+        {
+            return Err(Rejection::GasLimit);
+        }
+
+        let calldata_size = tx.tx.data().map_or(0, |data| data.len());
+        if config
+            .max_calldata_size
+            .is_some_and(|max| calldata_size > max)
+        {
+            return Err(Rejection::CalldataSize);
+        }
+
+        if let Some(quota) = &config.sender_rate_limit {
+            if !self.try_acquire(sender, quota) {
+                return Err(Rejection::RateLimited);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Spend one transaction of the sender quota, returns false if the sender is out of quota
+    fn try_acquire(&self, sender: &str, quota: &SenderQuota) -> bool {
+        let mut buckets = self.buckets.lock().expect("policy lock poisoned");
+        if buckets.len() >= DEFAULT_MAX_SENDERS && !buckets.contains_key(sender) {
+            // Full buckets are equal to the new ones and can be dropped
+            buckets.retain(|_, bucket| {
+                bucket.refill(quota);
+                bucket.txs < burst(quota)
+            });
+        }
+
+        let bucket = buckets.entry(sender.to_string()).or_insert_with(|| Bucket {
+            txs: burst(quota),
+            updated: Instant::now(),
+        });
+        bucket.refill(quota);
+        if bucket.txs < 1.0 {
+            return false;
+        }
+
+        bucket.txs -= 1.0;
+        true
+    }
+}
+
+/// Reload policies of the rollups when the config file changes
+pub async fn reload_loop(config_path: PathBuf, policies: HashMap<u64, Arc<Policy>>) {
+    let modified = |path: &PathBuf| -> Option<SystemTime> {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+
+    let mut last_modified = modified(&config_path);
+    loop {
+        tokio::time::sleep(RELOAD_INTERVAL).await;
+
+        let current = modified(&config_path);
+        if current == last_modified {
+            continue;
+        }
+        last_modified = current;
+
+        let rollups = match RheaConfig::read(&config_path)
+            .await
+            .and_then(|mut config| config.take_rollups())
+        {
+            Ok(rollups) => rollups,
+            Err(err) => {
+                tracing::warn!(
+                    "Failed to reload admission policies from {:?}, keeping the current ones: {:?}",
+                    config_path,
+                    err
+                );
+                continue;
+            }
+        };
+
+        for rollup in rollups {
+            if let Some(policy) = policies.get(&rollup.chain_id) {
+                policy.update(rollup.policy);
+                tracing::info!("Admission policy of rollup {} reloaded", rollup.chain_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::transaction::eip2718::TypedTransaction;
+    use ethers::types::{Eip1559TransactionRequest, Signature};
+
+    const GWEI: u64 = 1_000_000_000;
+
+    fn sender(n: u64) -> String {
+        format!("{:?}", Address::from_low_u64_be(n))
+    }
+
+    fn transaction(to: Option<Address>, fee_cap: u64, priority_fee: u64) -> QueuedTx {
+        let mut tx = Eip1559TransactionRequest::new()
+            .max_fee_per_gas(fee_cap)
+            .max_priority_fee_per_gas(priority_fee)
+            .gas(21_000)
+            .data(vec![0; 100]);
+        tx.to = to.map(Into::into);
+
+        QueuedTx {
+            hash: TxHash::zero(),
+            tx: TypedTransaction::Eip1559(tx),
+            signature: Signature {
+                r: U256::one(),
+                s: U256::one(),
+                v: 0,
+            },
+            received_at: 0,
+            seen: tokio::time::Instant::now(),
+        }
+    }
+
+    fn tx() -> QueuedTx {
+        transaction(Some(Address::from_low_u64_be(100)), 2 * GWEI, GWEI)
+    }
+
+    fn policy(config: PolicyConfig) -> Policy {
+        Policy::new(1001, config)
+    }
+
+    #[test]
+    fn everything_is_admitted_by_default() {
+        let policy = policy(PolicyConfig::default());
+        assert_eq!(policy.check(&sender(1), &tx()), Ok(()));
+        assert_eq!(policy.check(&sender(1), &transaction(None, 0, 0)), Ok(()));
+    }
+
+    #[test]
+    fn senders_are_allowed_and_denied() {
+        let policy = policy(PolicyConfig {
+            allowed_senders: Some(HashSet::from([
+                Address::from_low_u64_be(1),
+                Address::from_low_u64_be(2),
+            ])),
+            denied_senders: HashSet::from([Address::from_low_u64_be(2)]),
+            ..Default::default()
+        });
+
+        assert_eq!(policy.check(&sender(1), &tx()), Ok(()));
+        assert_eq!(
+            policy.check(&sender(2), &tx()),
+            Err(Rejection::SenderDenied)
+        );
+        assert_eq!(
+            policy.check(&sender(3), &tx()),
+            Err(Rejection::SenderNotAllowed)
+        );
+        assert_eq!(
+            policy.check("not an address", &tx()),
+            Err(Rejection::SenderNotAllowed)
+        );
+    }
+
+    #[test]
+    fn recipients_are_allowed_and_denied() {
+        let allowed = Address::from_low_u64_be(100);
+        let denied = Address::from_low_u64_be(101);
+        let policy = policy(PolicyConfig {
+            allowed_recipients: Some(HashSet::from([allowed, denied])),
+            denied_recipients: HashSet::from([denied]),
+            ..Default::default()
+        });
+
+        let to = |to| transaction(to, 2 * GWEI, GWEI);
+        assert_eq!(policy.check(&sender(1), &to(Some(allowed))), Ok(()));
+        assert_eq!(
+            policy.check(&sender(1), &to(Some(denied))),
+            Err(Rejection::RecipientDenied)
+        );
+        assert_eq!(
+            policy.check(&sender(1), &to(Some(Address::from_low_u64_be(102)))),
+            Err(Rejection::RecipientNotAllowed)
+        );
+        // Contract deployment
+        assert_eq!(
+            policy.check(&sender(1), &to(None)),
+            Err(Rejection::RecipientNotAllowed)
+        );
+    }
+
+    #[test]
+    fn fees_are_above_the_floor() {
+        let policy = policy(PolicyConfig {
+            min_gas_price: Some(2 * GWEI),
+            min_priority_fee: Some(GWEI),
+            ..Default::default()
+        });
+
+        let to = Some(Address::from_low_u64_be(100));
+        assert_eq!(
+            policy.check(&sender(1), &transaction(to, 2 * GWEI, GWEI)),
+            Ok(())
+        );
+        assert_eq!(
+            policy.check(&sender(1), &transaction(to, 2 * GWEI - 1, GWEI)),
+            Err(Rejection::GasPrice)
+        );
+        assert_eq!(
+            policy.check(&sender(1), &transaction(to, 2 * GWEI, GWEI - 1)),
+            Err(Rejection::PriorityFee)
+        );
+    }
+
+    #[test]
+    fn gas_and_calldata_are_capped() {
+        let policy = policy(PolicyConfig {
+            max_gas_limit: Some(21_000),
+            max_calldata_size: Some(100),
+            ..Default::default()
+        });
+        assert_eq!(policy.check(&sender(1), &tx()), Ok(()));
+
+        let mut gas = tx();
+        gas.tx.set_gas(21_001);
+        assert_eq!(policy.check(&sender(1), &gas), Err(Rejection::GasLimit));
+
+        let mut calldata = tx();
+        calldata.tx.set_data(vec![0; 101].into());
+        assert_eq!(
+            policy.check(&sender(1), &calldata),
+            Err(Rejection::CalldataSize)
+        );
+    }
+
+    #[test]
+    fn senders_are_rate_limited() {
+        let policy = policy(PolicyConfig {
+            sender_rate_limit: Some(SenderQuota {
+                txs_per_sec: 1,
+                burst: Some(2),
+            }),
+            ..Default::default()
+        });
+
+        assert_eq!(policy.check(&sender(1), &tx()), Ok(()));
+        assert_eq!(policy.check(&sender(1), &tx()), Ok(()));
+        assert_eq!(policy.check(&sender(1), &tx()), Err(Rejection::RateLimited));
+        // Every sender has its own bucket
+        assert_eq!(policy.check(&sender(2), &tx()), Ok(()));
+
+        // The bucket is refilled with time
+        policy
+            .buckets
+            .lock()
+            .unwrap()
+            .get_mut(&sender(1))
+            .unwrap()
+            .updated -= Duration::from_secs(1);
+        assert_eq!(policy.check(&sender(1), &tx()), Ok(()));
+        assert_eq!(policy.check(&sender(1), &tx()), Err(Rejection::RateLimited));
+    }
+
+    #[test]
+    fn updated_policy_keeps_rate_limits() {
+        let quota = SenderQuota {
+            txs_per_sec: 1,
+            burst: None,
+        };
+        let policy = policy(PolicyConfig {
+            sender_rate_limit: Some(quota.clone()),
+            ..Default::default()
+        });
+        assert_eq!(policy.check(&sender(1), &tx()), Ok(()));
+
+        policy.update(PolicyConfig {
+            sender_rate_limit: Some(quota),
+            ..Default::default()
+        });
+        assert_eq!(policy.check(&sender(1), &tx()), Err(Rejection::RateLimited));
+    }
+}
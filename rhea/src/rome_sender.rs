@@ -1,77 +1,606 @@
-use anyhow::bail;
-use ethers::types::TxHash;
-use rome_sdk::rome_evm_client::PayerConfig;
+use crate::config::RollupConfig;
+use crate::mempool_sender::QueuedTx;
+use crate::metrics::RPC_REQUESTS;
+use crate::shadow::{ShadowRecord, ShadowReport};
+use crate::store::unix_timestamp;
+use anyhow::{anyhow, bail};
+use async_trait::async_trait;
+use ethers::types::{Address, TransactionRequest, TxHash, U256};
+use rome_sdk::rome_evm_client::error::RomeEvmError;
+use rome_sdk::rome_evm_client::indexer::inmemory;
+use rome_sdk::rome_evm_client::rome_evm::error::RomeProgramError;
+use rome_sdk::rome_evm_client::{Payer, RomeEVMClient};
 use rome_sdk::rome_solana::config::SolanaConfig;
-use rome_sdk::{RheaTx, Rome, RomeConfig};
-use solana_sdk::commitment_config::CommitmentLevel;
+use rome_sdk::rome_solana::indexers::clock::SolanaClockIndexer;
+use rome_sdk::rome_solana::tower::SolanaTower;
+use rome_sdk::{EthSignedTxTuple, RheaTx, Rome, RomeConfig};
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_sdk::commitment_config::{CommitmentConfig, CommitmentLevel};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
 use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_FAILURES: u32 = 3;
+const DEFAULT_CIRCUIT_COOLDOWN_MS: u64 = 30_000;
+const DEFAULT_GAS_PRICE: u128 = 1_000_000_000;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+pub struct SubmissionConfig {
+    /// Number of consecutive transient failures after which a client is skipped
+    pub max_failures: Option<u32>,
+    /// Time a failing client is skipped for before it is tried again
+    pub circuit_cooldown_ms: Option<u64>,
+    /// If set, every transaction is submitted through this number of the best clients
+    /// concurrently and the first confirmation wins
+    pub hedge_fanout: Option<usize>,
+}
+
+/// Health of the Solana RPC client reported by the admin API
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientHealth {
+    pub url: String,
+    /// False while the circuit breaker skips the client
+    pub available: bool,
+    pub latency_ms: f64,
+    pub consecutive_failures: u32,
+    pub successes: u64,
+    pub failures: u64,
+}
+
+/// Rome client sending transactions through one Solana RPC
+struct RomeClient {
+    url: String,
+    rome: Rome,
+    latency_us: AtomicU64,
+    consecutive_failures: AtomicU32,
+    successes: AtomicU64,
+    failures: AtomicU64,
+    /// The client is skipped until this time
+    open_until: Mutex<Option<Instant>>,
+}
+
+impl RomeClient {
+    fn new(url: String, rome: Rome) -> Self {
+        Self {
+            url,
+            rome,
+            latency_us: AtomicU64::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            successes: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            open_until: Mutex::new(None),
+        }
+    }
+
+    fn is_available(&self) -> bool {
+        self.open_until
+            .lock()
+            .expect("circuit lock poisoned")
+            .map_or(true, |open_until| open_until <= Instant::now())
+    }
+
+    async fn send<'a>(
+        &self,
+        chain_id: u64,
+        hash: &TxHash,
+        sender_addr: &str,
+        rhea_tx: RheaTx<'a>,
+        config: &SubmissionConfig,
+        shadow: bool,
+    ) -> Result<(), SendError> {
+        let started = Instant::now();
+        let result = match self.rome.compose_rollup_tx(rhea_tx).await {
+            // Composed transactions are not sent in the shadow mode
+            Ok(_) if shadow => Ok(()),
+            Ok(mut rome_tx) => self
+                .rome
+                .send_and_confirm(&mut *rome_tx)
+                .await
+                .map_err(|err| SendError::Send(err.into())),
+            Err(err) => Err(SendError::Compose(err.into())),
+        };
+
+        RPC_REQUESTS
+            .with_label_values(&[
+                &chain_id.to_string(),
+                &self.url,
+                if result.is_ok() { "success" } else { "failure" },
+            ])
+            .inc();
+        match &result {
+            Ok(_) => {
+                tracing::info!(
+                    "SenderQueue {}: Transaction {:?} {} via {}",
+                    sender_addr,
+                    hash,
+                    if shadow {
+                        "composed"
+                    } else {
+                        "executed in Rome-EVM"
+                    },
+                    self.url
+                );
+                self.record_success(started.elapsed());
+            }
+            Err(err) => {
+                tracing::warn!(
+                    "SenderQueue {}: Transaction {:?} failed via {}: {}",
+                    sender_addr,
+                    hash,
+                    self.url,
+                    err
+                );
+                // Errors of the transaction itself say nothing about the client
+                if err.is_transient() {
+                    self.record_failure(config);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn record_success(&self, latency: Duration) {
+        self.successes.fetch_add(1, Ordering::Relaxed);
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        *self.open_until.lock().expect("circuit lock poisoned") = None;
+
+        let sample = latency.as_micros() as u64;
+        let _ = self
+            .latency_us
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                // Exponentially weighted moving average
+                Some(if current == 0 {
+                    sample
+                } else {
+                    (current * 7 + sample) / 8
+                })
+            });
+    }
+
+    fn record_failure(&self, config: &SubmissionConfig) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= config.max_failures.unwrap_or(DEFAULT_MAX_FAILURES) {
+            let cooldown = Duration::from_millis(
+                config
+                    .circuit_cooldown_ms
+                    .unwrap_or(DEFAULT_CIRCUIT_COOLDOWN_MS),
+            );
+            tracing::warn!(
+                "Solana RPC {} failed {} times in a row, skipping it for {:?}",
+                self.url,
+                failures,
+                cooldown
+            );
+            *self.open_until.lock().expect("circuit lock poisoned") =
+                Some(Instant::now() + cooldown);
+        }
+    }
+
+    fn health(&self) -> ClientHealth {
+        ClientHealth {
+            url: self.url.clone(),
+            available: self.is_available(),
+            latency_ms: self.latency_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            successes: self.successes.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Forwarding of transactions to Rome-EVM used by the sender queues
+#[async_trait]
+pub trait TxSender: Send + Sync + 'static {
+    /// Next nonce of the sender in Rome-EVM, reads the state with blocking calls
+    fn transaction_count(&self, chain_id: u64, address: Address) -> anyhow::Result<u64>;
+
+    /// Transactions are composed and emulated, but not sent
+    fn is_shadow(&self) -> bool;
+
+    /// Forward the transaction to Rome-EVM
+    async fn send_transaction(
+        &self,
+        chain_id: u64,
+        sender_addr: &str,
+        tx: &QueuedTx,
+    ) -> Result<(), SendError>;
+}
 
 pub struct RomeSender {
-    clients: Vec<Rome>,
+    clients: Vec<RomeClient>,
+    config: SubmissionConfig,
+    /// Clients reading Rome-EVM state of every rollup, one per Solana RPC
+    state_clients: HashMap<u64, Vec<RomeEVMClient>>,
+    /// Transactions are composed and emulated, but not sent
+    shadow: Option<Arc<ShadowReport>>,
 }
 
 impl RomeSender {
     pub async fn new(
         client_rpcs: Vec<url::Url>,
         commitment: CommitmentLevel,
-        rollups: &HashMap<u64, String>,
-        payers: &Vec<PayerConfig>,
+        rollup: &RollupConfig,
+        config: SubmissionConfig,
+        shadow: Option<Arc<ShadowReport>>,
     ) -> anyhow::Result<Self> {
+        let program_id = Pubkey::from_str(&rollup.program_id)?;
+        let rollups = HashMap::from([(rollup.chain_id, rollup.program_id.clone())]);
+        let ethereum_storage = match &rollup.ethereum_storage {
+            Some(ethereum_storage) => ethereum_storage.init()?,
+            None => Arc::new(inmemory::EthereumBlockStorage),
+        };
+        let gas_price = rollup.gas_price.unwrap_or(DEFAULT_GAS_PRICE);
+
         let mut clients = vec![];
+        let mut state_clients: HashMap<u64, Vec<RomeEVMClient>> = HashMap::new();
 
         for rpc_url in client_rpcs {
-            clients.push(
+            let rpc_client = Arc::new(RpcClient::new_with_commitment(
+                rpc_url.to_string(),
+                CommitmentConfig { commitment },
+            ));
+            let solana_clock_indexer = SolanaClockIndexer::new(rpc_client.clone()).await?;
+            state_clients
+                .entry(rollup.chain_id)
+                .or_default()
+                .push(RomeEVMClient::new(
+                    rollup.chain_id,
+                    program_id,
+                    SolanaTower::new(rpc_client.clone(), solana_clock_indexer.get_current_clock()),
+                    commitment,
+                    ethereum_storage.clone(),
+                    Payer::from_config_list(&rollup.payers).await?,
+                    gas_price.into(),
+                ));
+            tokio::spawn(async move {
+                if let Err(err) = solana_clock_indexer.start().await {
+                    tracing::warn!("Solana clock indexer stopped: {:?}", err);
+                }
+            });
+
+            clients.push(RomeClient::new(
+                rpc_url.to_string(),
                 Rome::new_with_config(RomeConfig {
                     solana_config: SolanaConfig {
                         rpc_url,
                         commitment,
                     },
                     rollups: rollups.clone(),
-                    payers: payers.clone(),
+                    payers: rollup.payers.clone(),
                 })
                 .await?,
-            );
+            ));
         }
 
-        Ok(Self { clients })
+        Ok(Self {
+            clients,
+            config,
+            state_clients,
+            shadow,
+        })
+    }
+
+    pub fn health(&self) -> Vec<ClientHealth> {
+        self.clients.iter().map(RomeClient::health).collect()
+    }
+
+    /// Clients in the order of preference: available ones first, faster ones first
+    fn ordered(&self) -> Vec<&RomeClient> {
+        let mut clients = self.clients.iter().collect::<Vec<_>>();
+        clients.sort_by_key(|client| {
+            (
+                !client.is_available(),
+                client.latency_us.load(Ordering::Relaxed),
+            )
+        });
+
+        clients
     }
 
-    pub async fn send_transaction<'a>(
+    /// Emulate the transaction in Rome-EVM, returns the estimated gas
+    fn emulate(&self, chain_id: u64, sender_addr: &str, tx: &QueuedTx) -> Result<U256, SendError> {
+        let Some(client) = self
+            .state_clients
+            .get(&chain_id)
+            .and_then(|clients| clients.first())
+        else {
+            return Err(SendError::Emulate(anyhow!("Unknown rollup {}", chain_id)));
+        };
+
+        let mut call = TransactionRequest::new()
+            .from(Address::from_str(sender_addr).unwrap_or_default())
+            .value(tx.tx.value().copied().unwrap_or_default())
+            .data(tx.tx.data().cloned().unwrap_or_default());
+        if let Some(to) = tx.tx.to_addr() {
+            call = call.to(*to);
+        }
+        if let Some(gas) = tx.tx.gas() {
+            call = call.gas(*gas);
+        }
+        if let Some(gas_price) = tx.tx.gas_price() {
+            call = call.gas_price(gas_price);
+        }
+
+        client
+            .call(&call)
+            .map_err(|err| SendError::Emulate(err.into()))?;
+        client
+            .estimate_gas(&call)
+            .map_err(|err| SendError::Emulate(err.into()))
+    }
+
+    async fn send<'a>(
         &self,
+        chain_id: u64,
         hash: &TxHash,
         sender_addr: &str,
         rhea_tx: RheaTx<'a>,
-    ) -> anyhow::Result<()> {
-        for client in &self.clients {
-            match client.compose_rollup_tx(rhea_tx.clone()).await {
-                Ok(mut rome_tx) => {
-                    if let Err(err) = client.send_and_confirm(&mut *rome_tx).await {
-                        tracing::warn!(
-                            "SenderQueue {}: Failed to send transaction {:?}: {:?}",
-                            sender_addr,
-                            hash,
-                            err
-                        )
-                    } else {
-                        tracing::info!(
-                            "SenderQueue {}: Transaction {:?} executed in Rome-EVM",
-                            sender_addr,
-                            hash,
-                        );
-                        return Ok(());
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!(
-                        "SenderQueue {}: Failed to compose transaction {:?}: {:?}",
-                        sender_addr,
+    ) -> Result<(), SendError> {
+        let shadow = self.is_shadow();
+        let clients = self.ordered();
+
+        if let Some(fanout) = self.config.hedge_fanout.filter(|fanout| *fanout > 1) {
+            let hedged = clients
+                .iter()
+                .take(fanout)
+                .map(|client| {
+                    Box::pin(client.send(
+                        chain_id,
                         hash,
-                        e
-                    );
+                        sender_addr,
+                        rhea_tx.clone(),
+                        &self.config,
+                        shadow,
+                    ))
+                })
+                .collect::<Vec<_>>();
+            if hedged.is_empty() {
+                return Err(SendError::Send(anyhow!("No Solana RPC clients")));
+            }
+
+            return futures::future::select_ok(hedged).await.map(|_| ());
+        }
+
+        let mut last_err = SendError::Send(anyhow!("No Solana RPC clients"));
+        for client in clients {
+            match client
+                .send(
+                    chain_id,
+                    hash,
+                    sender_addr,
+                    rhea_tx.clone(),
+                    &self.config,
+                    shadow,
+                )
+                .await
+            {
+                Ok(()) => return Ok(()),
+                // Other clients fail the same way
+                Err(err) if !err.is_transient() => return Err(err),
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+}
+
+#[async_trait]
+impl TxSender for RomeSender {
+    fn transaction_count(&self, chain_id: u64, address: Address) -> anyhow::Result<u64> {
+        let Some(clients) = self.state_clients.get(&chain_id) else {
+            bail!("Unknown rollup {}", chain_id);
+        };
+
+        let mut last_err = None;
+        for client in clients {
+            match client.transaction_count(address) {
+                Ok(nonce) => return Ok(nonce.as_u64()),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        bail!(
+            "Failed to get transaction count of {:?}: {:?}",
+            address,
+            last_err
+        )
+    }
+
+    fn is_shadow(&self) -> bool {
+        self.shadow.is_some()
+    }
+
+    /// Forward the transaction to Rome-EVM.
+    /// In the shadow mode the transaction is composed and emulated instead, the result is reported.
+    async fn send_transaction(
+        &self,
+        chain_id: u64,
+        sender_addr: &str,
+        tx: &QueuedTx,
+    ) -> Result<(), SendError> {
+        let rhea_tx = RheaTx::new(EthSignedTxTuple::new(tx.tx.clone(), tx.signature));
+        let Some(report) = &self.shadow else {
+            return self.send(chain_id, &tx.hash, sender_addr, rhea_tx).await;
+        };
+
+        let composed = self.send(chain_id, &tx.hash, sender_addr, rhea_tx).await;
+        let emulated = match &composed {
+            Ok(()) => self.emulate(chain_id, sender_addr, tx).map(Some),
+            Err(_) => Ok(None),
+        };
+        let result = composed.and(emulated);
+
+        report
+            .write(ShadowRecord {
+                chain_id,
+                hash: tx.hash,
+                sender: sender_addr.to_string(),
+                nonce: tx.tx.nonce().map_or(0, |nonce| nonce.as_u64()),
+                composed: !matches!(result, Err(SendError::Compose(_))),
+                success: result.is_ok(),
+                gas_used: result.as_ref().ok().copied().flatten(),
+                error: result.as_ref().err().map(ToString::to_string),
+                timestamp: unix_timestamp(),
+            })
+            .await;
+
+        result.map(|_| ())
+    }
+}
+
+/// Error of forwarding the transaction to Rome-EVM
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    #[error("Failed to compose transaction: {0:#}")]
+    Compose(anyhow::Error),
+
+    #[error("Failed to send transaction: {0:#}")]
+    Send(anyhow::Error),
+
+    #[error("Failed to emulate transaction: {0:#}")]
+    Emulate(anyhow::Error),
+}
+
+impl SendError {
+    /// The transaction may succeed if it is sent again.
+    /// Only failures of the Solana RPC and of the Solana cluster are transient,
+    /// other errors in the chain of sources are considered permanent.
+    pub fn is_transient(&self) -> bool {
+        let (SendError::Compose(err) | SendError::Send(err) | SendError::Emulate(err)) = self;
+        for err in err.chain() {
+            if let Some(err) = err.downcast_ref::<ClientError>() {
+                return is_transient_client_error(err);
+            }
+            if let Some(err) = err.downcast_ref::<TransactionError>() {
+                return is_transient_transaction_error(err);
+            }
+            if err.is::<std::io::Error>() || err.is::<tokio::time::error::Elapsed>() {
+                return true;
+            }
+            if let Some(err) = err.downcast_ref::<RomeEvmError>() {
+                if matches!(
+                    err,
+                    RomeEvmError::EmulationRevert(..) | RomeEvmError::EmulationError(_)
+                ) {
+                    return false;
                 }
             }
+            // The transaction is rejected by the Rome-EVM program
+            if err.is::<RomeProgramError>() {
+                return false;
+            }
         }
 
-        bail!("Failed to send transaction")
+        false
+    }
+}
+
+fn is_transient_client_error(err: &ClientError) -> bool {
+    if let Some(err) = err.get_transaction_error() {
+        return is_transient_transaction_error(&err);
+    }
+
+    // Signing, serialization and other errors fail the same way every time
+    matches!(
+        err.kind(),
+        ClientErrorKind::Io(_)
+            | ClientErrorKind::Reqwest(_)
+            | ClientErrorKind::RpcError(RpcError::RpcRequestError(_))
+            | ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                data: RpcResponseErrorData::NodeUnhealthy { .. },
+                ..
+            })
+    )
+}
+
+fn is_transient_transaction_error(err: &TransactionError) -> bool {
+    // Other errors are of the transaction itself
+    matches!(
+        err,
+        TransactionError::BlockhashNotFound
+            | TransactionError::AccountInUse
+            | TransactionError::WouldExceedMaxBlockCostLimit
+            | TransactionError::WouldExceedMaxAccountCostLimit
+            | TransactionError::WouldExceedAccountDataBlockLimit
+            | TransactionError::TooManyAccountLocks
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_error(err: impl Into<anyhow::Error>) -> SendError {
+        SendError::Send(err.into())
+    }
+
+    fn rpc_error(data: RpcResponseErrorData) -> ClientError {
+        ClientError::from(RpcError::RpcResponseError {
+            code: -32005,
+            message: "Node is unhealthy".to_string(),
+            data,
+        })
+    }
+
+    #[test]
+    fn rpc_failures_are_transient() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        assert!(send_error(ClientError::from(io)).is_transient());
+        assert!(send_error(ClientError::from(RpcError::RpcRequestError(
+            "timeout".to_string()
+        )))
+        .is_transient());
+        assert!(send_error(rpc_error(RpcResponseErrorData::NodeUnhealthy {
+            num_slots_behind: Some(10)
+        }))
+        .is_transient());
+        assert!(!send_error(rpc_error(RpcResponseErrorData::Empty)).is_transient());
+    }
+
+    #[test]
+    fn transaction_errors_are_classified() {
+        let transient = ClientError::from(TransactionError::BlockhashNotFound);
+        assert!(send_error(transient).is_transient());
+        assert!(SendError::Compose(TransactionError::AccountInUse.into()).is_transient());
+
+        let permanent = ClientError::from(TransactionError::InsufficientFundsForFee);
+        assert!(!send_error(permanent).is_transient());
+        assert!(!send_error(TransactionError::InvalidAccountForFee).is_transient());
+    }
+
+    #[test]
+    fn transaction_rejections_are_permanent() {
+        let revert = RomeEvmError::EmulationRevert("reverted".to_string(), "0x".to_string());
+        assert!(!SendError::Compose(revert.into()).is_transient());
+        assert!(
+            !SendError::Compose(RomeEvmError::EmulationError("out of gas".to_string()).into())
+                .is_transient()
+        );
+    }
+
+    #[test]
+    fn errors_found_in_the_chain() {
+        let err = anyhow::Error::from(ClientError::from(TransactionError::BlockhashNotFound))
+            .context("Failed to confirm transaction");
+        assert!(send_error(err).is_transient());
+
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        assert!(send_error(anyhow::Error::from(io).context("Failed to send")).is_transient());
+    }
+
+    #[test]
+    fn unknown_errors_are_permanent() {
+        assert!(!send_error(anyhow!("No Solana RPC clients")).is_transient());
+        assert!(!SendError::Emulate(anyhow!("Unknown rollup 1001")).is_transient());
     }
 }
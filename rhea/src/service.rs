@@ -1,70 +1,285 @@
-use crate::mempool::Mempool;
+use crate::api::admin::{start_rpc_server, RheaAdmin, RollupState};
+use crate::config::GethIndexerConfig;
+use crate::mempool::{Mempool, MempoolConfig};
+use crate::metrics::CHANNEL_OVERFLOWS;
+use crate::policy::{self, Policy};
 use crate::rome_sender::RomeSender;
-use rome_sdk::rome_geth::indexers::pending_txs::GethPendingTxsIndexer;
-use rome_sdk::rome_geth::types::{GethTxPoolReceiver, GethTxPoolSender};
-use rome_sdk::rome_utils::services::ServiceRunner;
+use crate::store::{MempoolStore, StoreConfig, StoreRetention};
+use ethers::providers::{Http, Middleware, Provider, Ws};
+use futures::StreamExt;
+use rome_sdk::rome_geth::types::GethTxPoolResult;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::sync::watch;
+
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+/// Latest txpool update with its sequence number
+type LatestTxPool = Option<(u64, Arc<GethTxPoolResult>)>;
+
+/// Geth endpoint polled for `txpool_content`
+enum TxPoolSource {
+    Http(Provider<Http>),
+    /// Connected on the first poll and after failures
+    Ws {
+        url: url::Url,
+        provider: Option<Provider<Ws>>,
+    },
+}
+
+impl TxPoolSource {
+    async fn txpool_content(&mut self) -> anyhow::Result<GethTxPoolResult> {
+        let content: serde_json::Value = match self {
+            TxPoolSource::Http(provider) => provider.request("txpool_content", ()).await?,
+            TxPoolSource::Ws { url, provider } => {
+                let provider = match provider {
+                    Some(provider) => provider,
+                    None => provider.insert(Provider::<Ws>::connect(url.as_str()).await?),
+                };
+                provider.request("txpool_content", ()).await?
+            }
+        };
+
+        Ok(serde_json::from_value(content)?)
+    }
+
+    fn disconnect(&mut self) {
+        if let TxPoolSource::Ws { provider, .. } = self {
+            *provider = None;
+        }
+    }
+}
+
+/// Rollup served by Rhea
+pub struct Rollup {
+    pub chain_id: u64,
+    pub rome: RomeSender,
+    pub geth_indexer: GethIndexerConfig,
+    pub geth_ws_url: Option<url::Url>,
+    pub geth_reconcile_interval: Duration,
+    pub mempool_config: MempoolConfig,
+    pub store_config: Option<StoreConfig>,
+    pub store_retention: StoreRetention,
+    pub policy: Arc<Policy>,
+}
 
 /// Listens to geth mempool channel and
 /// sends transaction to proxy
 /// then does a fork choice on geth
 pub struct RheaService;
 
-const DEFAULT_MEMPOOL_TTL_SEC: u64 = 300;
-
 impl RheaService {
-    /// Subscribe to rollup and listen to pending transactions
-    async fn subscribe_to_rollup(
-        geth_indexer: GethPendingTxsIndexer,
-        geth_pending_tx: GethTxPoolSender,
+    /// Apply the latest txpool update to the mempool, older ones are dropped if the mempool is behind
+    async fn mempool_loop(
+        chain_id: u64,
+        mempool: Arc<Mempool>,
+        mut latest_rx: watch::Receiver<LatestTxPool>,
+    ) {
+        let chain_id = chain_id.to_string();
+        let mut last_update = 0;
+        while latest_rx.changed().await.is_ok() {
+            let Some((update, result)) = latest_rx.borrow_and_update().clone() else {
+                continue;
+            };
+
+            let skipped = update - last_update - 1;
+            if skipped > 0 {
+                CHANNEL_OVERFLOWS
+                    .with_label_values(&[&chain_id, "geth_txpool", "drop_oldest"])
+                    .inc_by(skipped);
+            }
+            last_update = update;
+
+            mempool.update(&result).await;
+        }
+    }
+
+    /// Receive full bodies of new pending transactions from geth over WebSocket
+    async fn subscribe_to_pending_txs(ws_url: &url::Url, mempool: &Mempool) -> anyhow::Result<()> {
+        let provider = Provider::<Ws>::connect(ws_url.as_str()).await?;
+        let mut pending_txs = provider.subscribe_full_pending_txs().await?;
+        tracing::info!("Subscribed to geth pending transactions at {}", ws_url);
+
+        while let Some(tx) = pending_txs.next().await {
+            mempool.add_tx(&tx).await;
+        }
+
+        anyhow::bail!("Subscription closed")
+    }
+
+    /// Keep the pending transactions subscription alive
+    async fn pending_txs_loop(chain_id: u64, ws_url: url::Url, mempool: Arc<Mempool>) {
+        loop {
+            if let Err(err) = Self::subscribe_to_pending_txs(&ws_url, &mempool).await {
+                tracing::warn!(
+                    "Rollup {}: geth subscription at {} failed, txpool polling continues: {:?}",
+                    chain_id,
+                    ws_url,
+                    err
+                );
+            }
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+
+    /// Poll the whole txpool, the mempool takes only the latest update
+    async fn txpool_loop(
+        chain_id: u64,
+        mut source: TxPoolSource,
+        interval: Duration,
+        latest_tx: watch::Sender<LatestTxPool>,
     ) -> anyhow::Result<()> {
-        tracing::info!("Starting Geth Mempool Indexer");
-        geth_indexer
-            .listen(geth_pending_tx, ServiceRunner::default())
-            .await
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut update = 0;
+        loop {
+            ticker.tick().await;
+            let result = match source.txpool_content().await {
+                Ok(result) => result,
+                Err(err) => {
+                    tracing::warn!("Rollup {}: geth txpool poll failed: {:?}", chain_id, err);
+                    source.disconnect();
+                    continue;
+                }
+            };
+
+            update += 1;
+            if latest_tx.send(Some((update, Arc::new(result)))).is_err() {
+                anyhow::bail!("Mempool stopped");
+            }
+        }
     }
 
-    /// Listen to the mempool channel and send transactions to the rollup
-    async fn mempool_loop(
-        rome: Arc<RomeSender>,
-        mut geth_rx: GethTxPoolReceiver,
-        mempool_ttl: Option<Duration>,
+    /// Forward transactions of the rollup until its txpool polling stops.
+    /// With the WebSocket endpoint of geth transactions are received by the subscription
+    /// and the txpool is polled over it only to reconcile, otherwise the txpool is polled over HTTP.
+    async fn run_rollup(
+        chain_id: u64,
+        geth_indexer: GethIndexerConfig,
+        geth_ws_url: Option<url::Url>,
+        geth_reconcile_interval: Duration,
+        mempool: Arc<Mempool>,
     ) {
-        let mempool_ttl =
-            mempool_ttl.unwrap_or_else(|| Duration::from_secs(DEFAULT_MEMPOOL_TTL_SEC));
-        let mempool = Mempool::new(rome.clone(), mempool_ttl);
+        // Txpool updates are passed through a single slot, older ones are replaced by newer
+        let (latest_tx, latest_rx) = watch::channel(None);
+        let mempool_jh = tokio::spawn(Self::mempool_loop(chain_id, mempool.clone(), latest_rx));
 
-        while let Some(res) = geth_rx.recv().await {
-            let Some(result) = &res.result else {
-                tracing::warn!("Error in response: {:?}", res.error);
-                continue;
-            };
+        let mut pending_txs_jh = None;
+        let (source, interval) = match geth_ws_url {
+            Some(ws_url) => {
+                pending_txs_jh = Some(tokio::spawn(Self::pending_txs_loop(
+                    chain_id,
+                    ws_url.clone(),
+                    mempool,
+                )));
+                let source = TxPoolSource::Ws {
+                    url: ws_url,
+                    provider: None,
+                };
+                (source, geth_reconcile_interval)
+            }
+            None => {
+                tracing::info!("Rollup {}: polling geth txpool", chain_id);
+                let source =
+                    TxPoolSource::Http(Provider::new(Http::new(geth_indexer.geth_http_addr)));
+                (source, geth_indexer.poll_interval())
+            }
+        };
+        let txpool_jh = tokio::spawn(Self::txpool_loop(chain_id, source, interval, latest_tx));
+
+        tokio::select! {
+            res = txpool_jh => {
+                tracing::error!("Rollup {}: txpool polling stopped: {:?}", chain_id, res);
+            }
+            res = mempool_jh => {
+                tracing::error!("Rollup {}: mempool stopped unexpectedly: {:?}", chain_id, res);
+            },
+        }
 
-            mempool.update(result).await;
+        if let Some(pending_txs_jh) = pending_txs_jh {
+            pending_txs_jh.abort();
         }
     }
 
     /// Start the Rhea service
     pub async fn start(
-        rome: RomeSender,
-        geth_indexer: GethPendingTxsIndexer,
-        mempool_ttl: Option<Duration>,
+        rollups: Vec<Rollup>,
+        admin_rpc: Option<SocketAddr>,
+        config_path: PathBuf,
     ) -> anyhow::Result<()> {
         tracing::info!("Starting Rhea Service...");
 
-        let (geth_pending_tx, geth_pending_rx) = mpsc::unbounded_channel();
-        let geth_jh = Self::subscribe_to_rollup(geth_indexer, geth_pending_tx);
-        let mempool_jh = Self::mempool_loop(Arc::new(rome), geth_pending_rx, mempool_ttl);
+        let mut rollup_states = HashMap::new();
+        let mut policies = HashMap::new();
+        let mut rollup_jhs = Vec::with_capacity(rollups.len());
+        let mut store_files = HashSet::new();
+        for rollup in rollups {
+            if let Some(StoreConfig::File { path }) = &rollup.store_config {
+                if !store_files.insert(path.clone()) {
+                    anyhow::bail!(
+                        "Rollup {}: store file {} is used by another rollup",
+                        rollup.chain_id,
+                        path.display()
+                    );
+                }
+            }
 
-        tokio::select! {
-            res = tokio::spawn(geth_jh) => {
-                anyhow::bail!("Rollup subscription error: {:?}", res);
+            let (store, stored_state) = MempoolStore::open(
+                rollup.chain_id,
+                rollup.store_config.as_ref(),
+                rollup.store_retention,
+            )
+            .await?;
+            let store = Arc::new(store);
+            tokio::spawn(store.clone().prune_loop());
+
+            let rome = Arc::new(rollup.rome);
+            let mempool = Mempool::new(
+                rome.clone(),
+                rollup.mempool_config,
+                store,
+                stored_state,
+                rollup.policy.clone(),
+            )
+            .await;
+            policies.insert(rollup.chain_id, rollup.policy);
+
+            let state = RollupState {
+                mempool: mempool.clone(),
+                rome,
+            };
+            if rollup_states.insert(rollup.chain_id, state).is_some() {
+                anyhow::bail!("Rollup {} is configured twice", rollup.chain_id);
             }
-            res = tokio::spawn(mempool_jh) => {
-                anyhow::bail!("Geth pending transactions channel closed unexpectedly: {:?}", res);
-            },
+
+            tracing::info!("Serving rollup {}", rollup.chain_id);
+            rollup_jhs.push(tokio::spawn(Self::run_rollup(
+                rollup.chain_id,
+                rollup.geth_indexer,
+                rollup.geth_ws_url,
+                rollup.geth_reconcile_interval,
+                mempool,
+            )));
+        }
+
+        if rollup_jhs.is_empty() {
+            anyhow::bail!("No rollups configured");
         }
+
+        tokio::spawn(policy::reload_loop(config_path, policies));
+
+        let _admin_server = match admin_rpc {
+            Some(host) => {
+                Some(start_rpc_server(Arc::new(RheaAdmin::new(rollup_states)), host).await?)
+            }
+            None => None,
+        };
+
+        // Rollups are independent, a stopped one does not stop the others
+        futures::future::join_all(rollup_jhs).await;
+        anyhow::bail!("All rollups stopped");
     }
 }
@@ -0,0 +1,58 @@
+use ethers::types::{TxHash, U256};
+use std::path::Path;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Result of the transaction in the shadow mode
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowRecord {
+    pub chain_id: u64,
+    pub hash: TxHash,
+    pub sender: String,
+    pub nonce: u64,
+    /// The Rome transaction was composed
+    pub composed: bool,
+    /// The transaction was composed and emulated successfully, it would have been sent
+    pub success: bool,
+    /// Gas estimated by the emulation
+    pub gas_used: Option<U256>,
+    pub error: Option<String>,
+    /// Unix timestamp of the emulation
+    pub timestamp: u64,
+}
+
+/// Report of the shadow mode, one JSON record per transaction
+pub struct ShadowReport {
+    file: Mutex<File>,
+}
+
+impl ShadowReport {
+    pub async fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub async fn write(&self, record: ShadowRecord) {
+        let line = match serde_json::to_string(&record) {
+            Ok(line) => line + "\n",
+            Err(err) => {
+                tracing::warn!("Failed to encode {:?}: {:?}", record, err);
+                return;
+            }
+        };
+
+        let mut file = self.file.lock().await;
+        if let Err(err) = file.write_all(line.as_bytes()).await {
+            tracing::warn!("Failed to write the shadow report: {:?}", err);
+        }
+    }
+}
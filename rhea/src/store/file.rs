@@ -0,0 +1,349 @@
+use super::{StoreBackend, StoreRecord, StoredState};
+use async_trait::async_trait;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::Mutex;
+
+/// The log is never compacted below this number of records
+const MIN_COMPACTION_RECORDS: usize = 10_000;
+
+/// Open log with the state it describes
+struct Log {
+    file: File,
+    state: StoredState,
+    /// Number of records in the file
+    records: usize,
+}
+
+/// Append-only log of [StoreRecord]s, one JSON record per line.
+/// The log is compacted on startup and whenever it has grown twice the size of the state.
+pub struct FileStore {
+    path: PathBuf,
+    log: Mutex<Option<Log>>,
+}
+
+impl FileStore {
+    pub async fn open(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            log: Mutex::new(None),
+        })
+    }
+
+    async fn read(&self) -> anyhow::Result<StoredState> {
+        let mut state = StoredState::default();
+        let file = match File::open(&self.path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(state),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut lines = BufReader::new(file).lines();
+        while let Some(line) = lines.next_line().await? {
+            match serde_json::from_str::<StoreRecord>(&line) {
+                Ok(record) => state.apply(record),
+                // The last record may be incomplete if the process was killed while writing it
+                Err(err) => tracing::warn!("Skipping malformed mempool store record: {:?}", err),
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Rewrite the log with the current state only and open it for appending
+    async fn compact(&self, state: StoredState) -> anyhow::Result<Log> {
+        let tmp_path = self.path.with_extension("tmp");
+        let mut tmp = File::create(&tmp_path).await?;
+
+        for (hash, tx) in &state.txs {
+            let record = StoreRecord::Tx {
+                hash: *hash,
+                tx: tx.clone(),
+            };
+            tmp.write_all(line(&record)?.as_bytes()).await?;
+        }
+        for (sender, last) in &state.last_nonces {
+            let record = StoreRecord::Nonce {
+                sender: sender.clone(),
+                nonce: last.nonce,
+                updated_at: last.updated_at,
+            };
+            tmp.write_all(line(&record)?.as_bytes()).await?;
+        }
+        for (hash, letter) in &state.dead_letters {
+            let record = StoreRecord::DeadLetter {
+                hash: *hash,
+                letter: letter.clone(),
+            };
+            tmp.write_all(line(&record)?.as_bytes()).await?;
+        }
+
+        tmp.sync_all().await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+
+        Ok(Log {
+            file,
+            records: state.records(),
+            state,
+        })
+    }
+}
+
+fn line(record: &StoreRecord) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(record)? + "\n")
+}
+
+#[async_trait]
+impl StoreBackend for FileStore {
+    async fn load(&self) -> anyhow::Result<StoredState> {
+        let state = self.read().await?;
+        let log = self.compact(state.clone()).await?;
+        *self.log.lock().await = Some(log);
+
+        Ok(state)
+    }
+
+    async fn write(&self, record: StoreRecord) -> anyhow::Result<()> {
+        let mut log = self.log.lock().await;
+        let Some(current) = log.as_mut() else {
+            anyhow::bail!("Mempool store is not loaded");
+        };
+
+        current.file.write_all(line(&record)?.as_bytes()).await?;
+        current.file.flush().await?;
+        current.state.apply(record);
+        current.records += 1;
+
+        if current.records > MIN_COMPACTION_RECORDS.max(2 * current.state.records()) {
+            // The current log stays open if the compaction fails
+            let compacted = self.compact(current.state.clone()).await?;
+            *log = Some(compacted);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        unix_timestamp, DeadLetter, MempoolStore, StoreConfig, StoreRetention, StoredTx, TxStatus,
+    };
+    use ethers::types::{Bytes, TxHash};
+    use std::time::Duration;
+
+    /// Log in the temporary directory removed at the end of the test
+    struct TempLog(PathBuf);
+
+    impl TempLog {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "rhea-store-{}-{}.log",
+                std::process::id(),
+                name
+            ));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+
+        fn append(&self, records: &[StoreRecord]) {
+            let mut content = std::fs::read_to_string(&self.0).unwrap_or_default();
+            for record in records {
+                content += &line(record).unwrap();
+            }
+            std::fs::write(&self.0, content).unwrap();
+        }
+
+        fn lines(&self) -> usize {
+            std::fs::read_to_string(&self.0).unwrap().lines().count()
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    async fn open(log: &TempLog) -> (MempoolStore, StoredState) {
+        let config = StoreConfig::File {
+            path: log.0.clone(),
+        };
+        let retention = StoreRetention {
+            tx_ttl: Duration::from_secs(600),
+            nonce_ttl: Duration::from_secs(3600),
+            dead_letter_ttl: Duration::from_secs(3600),
+        };
+
+        MempoolStore::open(1001, Some(&config), retention)
+            .await
+            .unwrap()
+    }
+
+    fn hash(n: u64) -> TxHash {
+        TxHash::from_low_u64_be(n)
+    }
+
+    fn dead_letter(sender: &str, failed_at: u64) -> DeadLetter {
+        DeadLetter {
+            sender: sender.to_string(),
+            nonce: 5,
+            raw_tx: Bytes::from(vec![0xf8, 0x6b]),
+            error: "blockhash not found".to_string(),
+            attempts: 3,
+            first_seen: failed_at,
+            failed_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn state_is_restored_after_restart() {
+        let log = TempLog::new("restore");
+        let (store, state) = open(&log).await;
+        assert_eq!(state.records(), 0);
+
+        store.tx_received(hash(1), "0xa".to_string(), 0).await;
+        store.tx_forwarded(hash(1), "0xa", 0).await;
+        store.tx_received(hash(2), "0xa".to_string(), 1).await;
+        store.tx_received(hash(3), "0xb".to_string(), 5).await;
+        store
+            .tx_dead_lettered(hash(3), dead_letter("0xb", unix_timestamp()))
+            .await;
+        drop(store);
+
+        let (store, state) = open(&log).await;
+        assert_eq!(state.txs[&hash(1)].status, TxStatus::Forwarded);
+        assert_eq!(state.txs[&hash(3)].status, TxStatus::Failed);
+        assert_eq!(state.last_nonces["0xa"].nonce, 0);
+        assert_eq!(state.dead_letters[&hash(3)].attempts, 3);
+        assert_eq!(store.last_nonce("0xa"), Some(0));
+        assert_eq!(store.last_nonce("0xb"), None);
+
+        // Pending transaction is forgotten to be forwarded again
+        assert!(!state.txs.contains_key(&hash(2)));
+        assert!(store.tx(&hash(2)).is_none());
+        drop(store);
+
+        let (_, state) = open(&log).await;
+        assert!(!state.txs.contains_key(&hash(2)));
+        assert_eq!(state.records(), 4);
+        assert_eq!(log.lines(), 4);
+    }
+
+    #[tokio::test]
+    async fn malformed_last_record_is_skipped() {
+        let log = TempLog::new("malformed");
+        let (store, _) = open(&log).await;
+        store.tx_received(hash(1), "0xa".to_string(), 0).await;
+        store.tx_forwarded(hash(1), "0xa", 0).await;
+        drop(store);
+
+        let mut content = std::fs::read_to_string(&log.0).unwrap();
+        content += r#"{"type":"status","hash":"0x00"#;
+        std::fs::write(&log.0, content).unwrap();
+
+        let (_, state) = open(&log).await;
+        assert_eq!(state.txs[&hash(1)].status, TxStatus::Forwarded);
+        assert_eq!(state.last_nonces["0xa"].nonce, 0);
+    }
+
+    #[tokio::test]
+    async fn log_is_compacted_when_grown() {
+        let log = TempLog::new("compaction");
+        let (store, _) = open(&log).await;
+        store.tx_received(hash(1), "0xa".to_string(), 0).await;
+
+        for nonce in 0..MIN_COMPACTION_RECORDS as u64 {
+            store.tx_forwarded(hash(1), "0xa", nonce).await;
+        }
+        assert!(log.lines() <= MIN_COMPACTION_RECORDS);
+
+        drop(store);
+        let (_, state) = open(&log).await;
+        assert_eq!(state.txs[&hash(1)].status, TxStatus::Forwarded);
+        assert_eq!(
+            state.last_nonces["0xa"].nonce,
+            MIN_COMPACTION_RECORDS as u64 - 1
+        );
+    }
+
+    #[tokio::test]
+    async fn outdated_records_are_pruned() {
+        let log = TempLog::new("prune");
+        let now = unix_timestamp();
+        let day_ago = now - 24 * 3600;
+        let stored_tx = |received_at| StoredTx {
+            sender: "0xa".to_string(),
+            nonce: 0,
+            status: TxStatus::Forwarded,
+            received_at,
+        };
+        log.append(&[
+            StoreRecord::Tx {
+                hash: hash(1),
+                tx: stored_tx(day_ago),
+            },
+            StoreRecord::Tx {
+                hash: hash(2),
+                tx: stored_tx(now),
+            },
+            StoreRecord::Nonce {
+                sender: "0xa".to_string(),
+                nonce: 1,
+                updated_at: day_ago,
+            },
+            StoreRecord::Nonce {
+                sender: "0xb".to_string(),
+                nonce: 2,
+                updated_at: now,
+            },
+            StoreRecord::DeadLetter {
+                hash: hash(3),
+                letter: dead_letter("0xa", day_ago),
+            },
+            StoreRecord::DeadLetter {
+                hash: hash(4),
+                letter: dead_letter("0xb", now),
+            },
+        ]);
+
+        let (store, state) = open(&log).await;
+        assert_eq!(state.txs.keys().collect::<Vec<_>>(), vec![&hash(2)]);
+        assert_eq!(state.last_nonces.keys().collect::<Vec<_>>(), vec!["0xb"]);
+        assert_eq!(
+            state.dead_letters.keys().collect::<Vec<_>>(),
+            vec![&hash(4)]
+        );
+        assert!(store.dead_letter(&hash(3)).is_none());
+        drop(store);
+
+        let (_, state) = open(&log).await;
+        assert_eq!(state.records(), 3);
+    }
+
+    #[tokio::test]
+    async fn records_without_timestamps_are_kept() {
+        let log = TempLog::new("legacy");
+        std::fs::write(
+            &log.0,
+            format!(
+                "{}\n{}\n",
+                r#"{"type":"tx","hash":"0x0000000000000000000000000000000000000000000000000000000000000001","sender":"0xa","nonce":0,"status":"forwarded"}"#,
+                r#"{"type":"nonce","sender":"0xa","nonce":0}"#
+            ),
+        )
+        .unwrap();
+
+        let (_, state) = open(&log).await;
+        assert_eq!(state.txs[&hash(1)].status, TxStatus::Forwarded);
+        assert_eq!(state.last_nonces["0xa"].nonce, 0);
+    }
+}
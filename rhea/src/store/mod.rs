@@ -0,0 +1,475 @@
+mod file;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "postgres")]
+mod schema;
+
+use async_trait::async_trait;
+use ethers::types::{Bytes, TxHash};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Durable storage of the mempool state
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StoreConfig {
+    /// Append-only log in the local file
+    File { path: PathBuf },
+    /// Postgres database, requires the `postgres` feature
+    Postgres { database_url: String },
+}
+
+/// Forwarding status of the transaction
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    /// Received from geth, not yet forwarded
+    Pending,
+    /// Executed in Rome-EVM
+    Forwarded,
+    /// Composed and emulated in the shadow mode, not sent
+    Emulated,
+    /// Failed to forward
+    Failed,
+    /// Replaced by the transaction with the same nonce and a higher fee
+    Replaced,
+    /// Dropped by the operator
+    Dropped,
+    /// Rejected by the admission policy
+    Rejected,
+}
+
+impl TxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TxStatus::Pending => "pending",
+            TxStatus::Forwarded => "forwarded",
+            TxStatus::Emulated => "emulated",
+            TxStatus::Failed => "failed",
+            TxStatus::Replaced => "replaced",
+            TxStatus::Dropped => "dropped",
+            TxStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(status: &str) -> anyhow::Result<Self> {
+        match status {
+            "pending" => Ok(TxStatus::Pending),
+            "forwarded" => Ok(TxStatus::Forwarded),
+            "emulated" => Ok(TxStatus::Emulated),
+            "failed" => Ok(TxStatus::Failed),
+            "replaced" => Ok(TxStatus::Replaced),
+            "dropped" => Ok(TxStatus::Dropped),
+            "rejected" => Ok(TxStatus::Rejected),
+            _ => anyhow::bail!("Unknown transaction status {}", status),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct StoredTx {
+    pub sender: String,
+    pub nonce: u64,
+    pub status: TxStatus,
+    /// Unix timestamp of receiving the transaction
+    #[serde(default = "unix_timestamp")]
+    pub received_at: u64,
+}
+
+/// Nonce of the last transaction of the sender executed in Rome-EVM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastNonce {
+    pub nonce: u64,
+    /// Unix timestamp of the last update
+    pub updated_at: u64,
+}
+
+/// How long the records of the mempool store are kept
+#[derive(Debug, Clone)]
+pub struct StoreRetention {
+    /// Transactions are forgotten by the mempool after its TTL, leftovers are pruned after this time
+    pub tx_ttl: Duration,
+    /// Last nonces of the senders without new transactions
+    pub nonce_ttl: Duration,
+    /// Dead letters since the last failure
+    pub dead_letter_ttl: Duration,
+}
+
+/// Transaction failed to be forwarded with all retries
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeadLetter {
+    pub sender: String,
+    pub nonce: u64,
+    /// Signed RLP-encoded transaction
+    pub raw_tx: Bytes,
+    /// Last error of sending the transaction
+    pub error: String,
+    pub attempts: u32,
+    /// Unix timestamp of receiving the transaction
+    pub first_seen: u64,
+    /// Unix timestamp of the last attempt
+    pub failed_at: u64,
+}
+
+/// Mempool state loaded on startup
+#[derive(Debug, Default, Clone)]
+pub struct StoredState {
+    pub txs: HashMap<TxHash, StoredTx>,
+    pub last_nonces: HashMap<String, LastNonce>,
+    pub dead_letters: HashMap<TxHash, DeadLetter>,
+}
+
+impl StoredState {
+    pub fn apply(&mut self, record: StoreRecord) {
+        match record {
+            StoreRecord::Tx { hash, tx } => {
+                self.txs.insert(hash, tx);
+            }
+            StoreRecord::Status { hash, status } => {
+                if let Some(tx) = self.txs.get_mut(&hash) {
+                    tx.status = status;
+                }
+            }
+            StoreRecord::Nonce {
+                sender,
+                nonce,
+                updated_at,
+            } => {
+                let last_nonce = self
+                    .last_nonces
+                    .entry(sender)
+                    .or_insert(LastNonce { nonce, updated_at });
+                last_nonce.nonce = last_nonce.nonce.max(nonce);
+                last_nonce.updated_at = last_nonce.updated_at.max(updated_at);
+            }
+            StoreRecord::Remove { hash } => {
+                self.txs.remove(&hash);
+            }
+            StoreRecord::RemoveNonce { sender } => {
+                self.last_nonces.remove(&sender);
+            }
+            StoreRecord::DeadLetter { hash, letter } => {
+                self.dead_letters.insert(hash, letter);
+            }
+            StoreRecord::Purge { hash } => {
+                self.dead_letters.remove(&hash);
+            }
+        }
+    }
+
+    /// Number of records describing the state
+    pub fn records(&self) -> usize {
+        self.txs.len() + self.last_nonces.len() + self.dead_letters.len()
+    }
+
+    /// Records removing the state outdated at the unix timestamp
+    fn expired(&self, now: u64, retention: &StoreRetention) -> Vec<StoreRecord> {
+        let expired = |timestamp: u64, ttl: Duration| timestamp + ttl.as_secs() < now;
+
+        let txs = self
+            .txs
+            .iter()
+            .filter(|(_, tx)| expired(tx.received_at, retention.tx_ttl))
+            .map(|(hash, _)| StoreRecord::Remove { hash: *hash });
+        let nonces = self
+            .last_nonces
+            .iter()
+            .filter(|(_, last)| expired(last.updated_at, retention.nonce_ttl))
+            .map(|(sender, _)| StoreRecord::RemoveNonce {
+                sender: sender.clone(),
+            });
+        let dead_letters = self
+            .dead_letters
+            .iter()
+            .filter(|(_, letter)| expired(letter.failed_at, retention.dead_letter_ttl))
+            .map(|(hash, _)| StoreRecord::Purge { hash: *hash });
+
+        txs.chain(nonces).chain(dead_letters).collect()
+    }
+}
+
+/// Change of the mempool state
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StoreRecord {
+    Tx {
+        hash: TxHash,
+        #[serde(flatten)]
+        tx: StoredTx,
+    },
+    Status {
+        hash: TxHash,
+        status: TxStatus,
+    },
+    Nonce {
+        sender: String,
+        nonce: u64,
+        #[serde(default = "unix_timestamp")]
+        updated_at: u64,
+    },
+    Remove {
+        hash: TxHash,
+    },
+    RemoveNonce {
+        sender: String,
+    },
+    DeadLetter {
+        hash: TxHash,
+        #[serde(flatten)]
+        letter: DeadLetter,
+    },
+    Purge {
+        hash: TxHash,
+    },
+}
+
+#[async_trait]
+pub trait StoreBackend: Send + Sync {
+    /// Load the mempool state
+    async fn load(&self) -> anyhow::Result<StoredState>;
+
+    /// Persist the change of the mempool state
+    async fn write(&self, record: StoreRecord) -> anyhow::Result<()>;
+}
+
+/// Mempool state shared by the mempool, the sender queues and the admin API.
+/// Without a backend the state is kept in memory only.
+pub struct MempoolStore {
+    backend: Option<Box<dyn StoreBackend>>,
+    state: Mutex<StoredState>,
+    retention: StoreRetention,
+}
+
+impl MempoolStore {
+    /// Open the store and load the state persisted before the restart.
+    /// Transactions which were not forwarded before the restart are forgotten
+    /// and forwarded again once received from geth.
+    pub async fn open(
+        chain_id: u64,
+        config: Option<&StoreConfig>,
+        retention: StoreRetention,
+    ) -> anyhow::Result<(Self, StoredState)> {
+        let backend: Option<Box<dyn StoreBackend>> = match config {
+            None => None,
+            Some(StoreConfig::File { path }) => Some(Box::new(file::FileStore::open(path).await?)),
+            #[cfg(feature = "postgres")]
+            Some(StoreConfig::Postgres { database_url }) => Some(Box::new(
+                postgres::PostgresStore::open(database_url, chain_id).await?,
+            )),
+            #[cfg(not(feature = "postgres"))]
+            Some(StoreConfig::Postgres { .. }) => {
+                anyhow::bail!("Rhea is built without the postgres feature")
+            }
+        };
+
+        let state = match &backend {
+            Some(backend) => backend.load().await?,
+            None => StoredState::default(),
+        };
+        tracing::info!(
+            "Rollup {}: mempool store loaded: {} transactions, {} senders, {} dead letters",
+            chain_id,
+            state.txs.len(),
+            state.last_nonces.len(),
+            state.dead_letters.len()
+        );
+
+        let store = Self {
+            backend,
+            state: Mutex::new(state.clone()),
+            retention,
+        };
+        let pending = state
+            .txs
+            .iter()
+            .filter(|(_, tx)| tx.status == TxStatus::Pending)
+            .map(|(hash, _)| *hash)
+            .collect::<Vec<_>>();
+        store.remove_txs(&pending).await;
+        store.prune().await;
+
+        let state = store.state.lock().expect("store lock poisoned").clone();
+        Ok((store, state))
+    }
+
+    /// Remove outdated transactions, last nonces and dead letters
+    pub async fn prune(&self) {
+        let expired = self
+            .state
+            .lock()
+            .expect("store lock poisoned")
+            .expired(unix_timestamp(), &self.retention);
+        if expired.is_empty() {
+            return;
+        }
+
+        tracing::info!("Pruning {} outdated mempool store records", expired.len());
+        for record in expired {
+            self.write(record).await;
+        }
+    }
+
+    /// Periodically prune the outdated state
+    pub async fn prune_loop(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.prune().await;
+        }
+    }
+
+    /// Nonce of the last transaction of the sender executed in Rome-EVM
+    pub fn last_nonce(&self, sender: &str) -> Option<u64> {
+        self.state
+            .lock()
+            .expect("store lock poisoned")
+            .last_nonces
+            .get(sender)
+            .map(|last| last.nonce)
+    }
+
+    /// Known transaction with its forwarding status
+    pub fn tx(&self, hash: &TxHash) -> Option<StoredTx> {
+        self.state
+            .lock()
+            .expect("store lock poisoned")
+            .txs
+            .get(hash)
+            .cloned()
+    }
+
+    pub async fn tx_received(&self, hash: TxHash, sender: String, nonce: u64) {
+        self.write(StoreRecord::Tx {
+            hash,
+            tx: StoredTx {
+                sender,
+                nonce,
+                status: TxStatus::Pending,
+                received_at: unix_timestamp(),
+            },
+        })
+        .await
+    }
+
+    pub async fn tx_forwarded(&self, hash: TxHash, sender: &str, nonce: u64) {
+        self.write(StoreRecord::Status {
+            hash,
+            status: TxStatus::Forwarded,
+        })
+        .await;
+        self.write(StoreRecord::Nonce {
+            sender: sender.to_string(),
+            nonce,
+            updated_at: unix_timestamp(),
+        })
+        .await
+    }
+
+    /// The nonce of the sender is not recorded, the transaction is not executed in Rome-EVM
+    pub async fn tx_emulated(&self, hash: TxHash) {
+        self.write(StoreRecord::Status {
+            hash,
+            status: TxStatus::Emulated,
+        })
+        .await
+    }
+
+    pub async fn tx_failed(&self, hash: TxHash) {
+        self.write(StoreRecord::Status {
+            hash,
+            status: TxStatus::Failed,
+        })
+        .await
+    }
+
+    pub async fn tx_replaced(&self, hash: TxHash) {
+        self.write(StoreRecord::Status {
+            hash,
+            status: TxStatus::Replaced,
+        })
+        .await
+    }
+
+    pub async fn tx_dropped(&self, hash: TxHash) {
+        self.write(StoreRecord::Status {
+            hash,
+            status: TxStatus::Dropped,
+        })
+        .await
+    }
+
+    pub async fn tx_rejected(&self, hash: TxHash) {
+        self.write(StoreRecord::Status {
+            hash,
+            status: TxStatus::Rejected,
+        })
+        .await
+    }
+
+    pub async fn tx_dead_lettered(&self, hash: TxHash, letter: DeadLetter) {
+        self.tx_failed(hash).await;
+        self.write(StoreRecord::DeadLetter { hash, letter }).await
+    }
+
+    pub fn dead_letters(&self) -> HashMap<TxHash, DeadLetter> {
+        self.state
+            .lock()
+            .expect("store lock poisoned")
+            .dead_letters
+            .clone()
+    }
+
+    pub fn dead_letter(&self, hash: &TxHash) -> Option<DeadLetter> {
+        self.state
+            .lock()
+            .expect("store lock poisoned")
+            .dead_letters
+            .get(hash)
+            .cloned()
+    }
+
+    /// Remove the dead letter, returns false if it is unknown
+    pub async fn purge_dead_letter(&self, hash: TxHash) -> bool {
+        if self.dead_letter(&hash).is_none() {
+            return false;
+        }
+
+        self.write(StoreRecord::Purge { hash }).await;
+        true
+    }
+
+    pub async fn remove_txs(&self, hashes: &[TxHash]) {
+        for hash in hashes {
+            self.write(StoreRecord::Remove { hash: *hash }).await
+        }
+    }
+
+    async fn write(&self, record: StoreRecord) {
+        self.state
+            .lock()
+            .expect("store lock poisoned")
+            .apply(record.clone());
+
+        let Some(backend) = &self.backend else {
+            return;
+        };
+
+        // Failures of the store must not stop forwarding of transactions
+        if let Err(err) = backend.write(record.clone()).await {
+            tracing::warn!("Failed to persist {:?}: {:?}", record, err);
+        }
+    }
+}
+
+/// Current unix timestamp in seconds
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
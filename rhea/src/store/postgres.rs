@@ -0,0 +1,226 @@
+use super::schema::{rhea_dead_letters, rhea_nonces, rhea_txs};
+use super::{DeadLetter, LastNonce, StoreBackend, StoreRecord, StoredState, StoredTx, TxStatus};
+use async_trait::async_trait;
+use diesel::pg::upsert::excluded;
+use diesel::pg::PgConnection;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::sql_types::BigInt;
+use ethers::types::{Bytes, TxHash};
+use std::str::FromStr;
+
+define_sql_function!(fn greatest(a: BigInt, b: BigInt) -> BigInt);
+
+#[derive(Queryable, Insertable)]
+#[diesel(table_name = rhea_txs)]
+struct TxRow {
+    chain_id: i64,
+    hash: String,
+    sender: String,
+    nonce: i64,
+    status: String,
+    received_at: i64,
+}
+
+#[derive(Queryable, Insertable)]
+#[diesel(table_name = rhea_nonces)]
+struct NonceRow {
+    chain_id: i64,
+    sender: String,
+    nonce: i64,
+    updated_at: i64,
+}
+
+#[derive(Queryable, Insertable)]
+#[diesel(table_name = rhea_dead_letters)]
+struct DeadLetterRow {
+    chain_id: i64,
+    hash: String,
+    sender: String,
+    nonce: i64,
+    raw_tx: String,
+    error: String,
+    attempts: i64,
+    first_seen: i64,
+    failed_at: i64,
+}
+
+/// Mempool state stored in the Postgres database.
+/// Tables are created by the migrations in `rhea/migrations`,
+/// rollups sharing the database are told apart by the chain id.
+pub struct PostgresStore {
+    pool: Pool<ConnectionManager<PgConnection>>,
+    chain_id: i64,
+}
+
+impl PostgresStore {
+    pub async fn open(database_url: &str, chain_id: u64) -> anyhow::Result<Self> {
+        let manager = ConnectionManager::<PgConnection>::new(database_url);
+        let pool = tokio::task::spawn_blocking(move || Pool::builder().build(manager)).await??;
+
+        Ok(Self {
+            pool,
+            chain_id: chain_id as i64,
+        })
+    }
+
+    async fn run<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut PgConnection) -> anyhow::Result<T> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get()?;
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+fn hash_key(hash: &TxHash) -> String {
+    format!("{:?}", hash)
+}
+
+#[async_trait]
+impl StoreBackend for PostgresStore {
+    async fn load(&self) -> anyhow::Result<StoredState> {
+        let chain_id = self.chain_id;
+        let (txs, nonces, dead_letters) = self
+            .run(move |conn| {
+                let txs = rhea_txs::table
+                    .filter(rhea_txs::chain_id.eq(chain_id))
+                    .load::<TxRow>(conn)?;
+                let nonces = rhea_nonces::table
+                    .filter(rhea_nonces::chain_id.eq(chain_id))
+                    .load::<NonceRow>(conn)?;
+                let dead_letters = rhea_dead_letters::table
+                    .filter(rhea_dead_letters::chain_id.eq(chain_id))
+                    .load::<DeadLetterRow>(conn)?;
+                Ok((txs, nonces, dead_letters))
+            })
+            .await?;
+
+        let mut state = StoredState::default();
+        for row in txs {
+            state.txs.insert(
+                TxHash::from_str(&row.hash)?,
+                StoredTx {
+                    sender: row.sender,
+                    nonce: row.nonce as u64,
+                    status: TxStatus::parse(&row.status)?,
+                    received_at: row.received_at as u64,
+                },
+            );
+        }
+        for row in nonces {
+            state.last_nonces.insert(
+                row.sender,
+                LastNonce {
+                    nonce: row.nonce as u64,
+                    updated_at: row.updated_at as u64,
+                },
+            );
+        }
+        for row in dead_letters {
+            state.dead_letters.insert(
+                TxHash::from_str(&row.hash)?,
+                DeadLetter {
+                    sender: row.sender,
+                    nonce: row.nonce as u64,
+                    raw_tx: Bytes::from_str(&row.raw_tx)?,
+                    error: row.error,
+                    attempts: row.attempts as u32,
+                    first_seen: row.first_seen as u64,
+                    failed_at: row.failed_at as u64,
+                },
+            );
+        }
+
+        Ok(state)
+    }
+
+    async fn write(&self, record: StoreRecord) -> anyhow::Result<()> {
+        let chain_id = self.chain_id;
+        self.run(move |conn| {
+            match record {
+                StoreRecord::Tx { hash, tx } => diesel::insert_into(rhea_txs::table)
+                    .values(TxRow {
+                        chain_id,
+                        hash: hash_key(&hash),
+                        sender: tx.sender,
+                        nonce: tx.nonce as i64,
+                        status: tx.status.as_str().to_string(),
+                        received_at: tx.received_at as i64,
+                    })
+                    .on_conflict((rhea_txs::chain_id, rhea_txs::hash))
+                    .do_update()
+                    .set(rhea_txs::status.eq(excluded(rhea_txs::status)))
+                    .execute(conn)?,
+                StoreRecord::Status { hash, status } => {
+                    diesel::update(rhea_txs::table.find((chain_id, hash_key(&hash))))
+                        .set(rhea_txs::status.eq(status.as_str()))
+                        .execute(conn)?
+                }
+                StoreRecord::Nonce {
+                    sender,
+                    nonce,
+                    updated_at,
+                } => diesel::insert_into(rhea_nonces::table)
+                    .values(NonceRow {
+                        chain_id,
+                        sender,
+                        nonce: nonce as i64,
+                        updated_at: updated_at as i64,
+                    })
+                    .on_conflict((rhea_nonces::chain_id, rhea_nonces::sender))
+                    .do_update()
+                    .set((
+                        rhea_nonces::nonce
+                            .eq(greatest(rhea_nonces::nonce, excluded(rhea_nonces::nonce))),
+                        rhea_nonces::updated_at.eq(greatest(
+                            rhea_nonces::updated_at,
+                            excluded(rhea_nonces::updated_at),
+                        )),
+                    ))
+                    .execute(conn)?,
+                StoreRecord::Remove { hash } => {
+                    diesel::delete(rhea_txs::table.find((chain_id, hash_key(&hash))))
+                        .execute(conn)?
+                }
+                StoreRecord::RemoveNonce { sender } => {
+                    diesel::delete(rhea_nonces::table.find((chain_id, sender))).execute(conn)?
+                }
+                StoreRecord::DeadLetter { hash, letter } => {
+                    diesel::insert_into(rhea_dead_letters::table)
+                        .values(DeadLetterRow {
+                            chain_id,
+                            hash: hash_key(&hash),
+                            sender: letter.sender,
+                            nonce: letter.nonce as i64,
+                            raw_tx: letter.raw_tx.to_string(),
+                            error: letter.error,
+                            attempts: letter.attempts as i64,
+                            first_seen: letter.first_seen as i64,
+                            failed_at: letter.failed_at as i64,
+                        })
+                        .on_conflict((rhea_dead_letters::chain_id, rhea_dead_letters::hash))
+                        .do_update()
+                        .set((
+                            rhea_dead_letters::error.eq(excluded(rhea_dead_letters::error)),
+                            rhea_dead_letters::attempts.eq(excluded(rhea_dead_letters::attempts)),
+                            rhea_dead_letters::failed_at.eq(excluded(rhea_dead_letters::failed_at)),
+                        ))
+                        .execute(conn)?
+                }
+                StoreRecord::Purge { hash } => {
+                    diesel::delete(rhea_dead_letters::table.find((chain_id, hash_key(&hash))))
+                        .execute(conn)?
+                }
+            };
+
+            Ok(())
+        })
+        .await
+    }
+}
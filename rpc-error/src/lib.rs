@@ -0,0 +1,188 @@
+//! JSON-RPC errors shared by the Rome services
+
+use jsonrpsee::types::error::INTERNAL_ERROR_CODE;
+use jsonrpsee::types::ErrorObjectOwned;
+use rome_sdk::rome_evm_client::{error::RomeEvmError, rome_evm::error::RomeProgramError};
+use serde::Serialize;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_sdk::transaction::TransactionError;
+
+// EIP-1474 error codes, geth uses -32000 for invalid inputs.
+// Errors sharing a code are told apart by the `reason` of the error data.
+pub const EXECUTION_REVERTED_CODE: i32 = 3;
+pub const INVALID_INPUT_CODE: i32 = -32000;
+pub const RESOURCE_UNAVAILABLE_CODE: i32 = -32002;
+pub const TRANSACTION_REJECTED_CODE: i32 = -32003;
+pub const LIMIT_EXCEEDED_CODE: i32 = -32005;
+
+/// Machine-readable details of the error returned in the `data` field
+#[derive(Serialize)]
+struct ErrorData {
+    source: &'static str,
+    reason: &'static str,
+}
+
+/// Error object with the source and the reason of the error in the `data` field
+pub fn error_object(
+    code: i32,
+    message: String,
+    source: &'static str,
+    reason: &'static str,
+) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(code, message, Some(ErrorData { source, reason }))
+}
+
+/// Error object of the Rome-EVM SDK error, reverts are returned the same way as by geth
+pub fn rome_evm_error(err: RomeEvmError) -> ErrorObjectOwned {
+    match err {
+        RomeEvmError::EmulationRevert(mes, data) => {
+            ErrorObjectOwned::owned(EXECUTION_REVERTED_CODE, mes, Some(data))
+        }
+        RomeEvmError::EmulationError(err) => {
+            ErrorObjectOwned::owned(EXECUTION_REVERTED_CODE, err, None::<String>)
+        }
+        err => {
+            let (code, reason) = rome_evm_error_code(&err);
+            error_object(code, err.to_string(), "rome_evm", reason)
+        }
+    }
+}
+
+/// Error object of the Rome-EVM program error
+pub fn rome_program_error(err: RomeProgramError) -> ErrorObjectOwned {
+    error_object(
+        TRANSACTION_REJECTED_CODE,
+        err.to_string(),
+        "rome_program",
+        "program_rejected",
+    )
+}
+
+/// Error object of the Solana RPC client error
+pub fn solana_error(err: ClientError) -> ErrorObjectOwned {
+    let (code, reason) = solana_error_code(&err);
+    error_object(code, err.to_string(), "solana", reason)
+}
+
+/// Classify the Rome-EVM SDK error by the Solana or Rome-EVM program error it wraps
+fn rome_evm_error_code(err: &RomeEvmError) -> (i32, &'static str) {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<ClientError>() {
+            return solana_error_code(err);
+        }
+        if let Some(err) = err.downcast_ref::<TransactionError>() {
+            return transaction_error_code(err);
+        }
+        if err.is::<RomeProgramError>() {
+            return (TRANSACTION_REJECTED_CODE, "program_rejected");
+        }
+
+        source = err.source();
+    }
+
+    (INTERNAL_ERROR_CODE, "internal_error")
+}
+
+fn transaction_error_code(err: &TransactionError) -> (i32, &'static str) {
+    match err {
+        TransactionError::InsufficientFundsForFee
+        | TransactionError::InsufficientFundsForRent { .. } => {
+            (RESOURCE_UNAVAILABLE_CODE, "payer_insufficient_lamports")
+        }
+        TransactionError::BlockhashNotFound => (RESOURCE_UNAVAILABLE_CODE, "blockhash_not_found"),
+        TransactionError::AlreadyProcessed => (INVALID_INPUT_CODE, "already_known"),
+        _ => (TRANSACTION_REJECTED_CODE, "solana_transaction_rejected"),
+    }
+}
+
+fn solana_error_code(err: &ClientError) -> (i32, &'static str) {
+    if let Some(err) = err.get_transaction_error() {
+        return transaction_error_code(&err);
+    }
+
+    match err.kind() {
+        ClientErrorKind::Io(_)
+        | ClientErrorKind::Reqwest(_)
+        | ClientErrorKind::RpcError(RpcError::RpcRequestError(_))
+        | ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            data: RpcResponseErrorData::NodeUnhealthy { .. },
+            ..
+        }) => (RESOURCE_UNAVAILABLE_CODE, "solana_unavailable"),
+        _ => (INTERNAL_ERROR_CODE, "internal_error"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::rpc_request::RpcResponseErrorData;
+    use solana_sdk::instruction::InstructionError;
+
+    fn data(err: &ErrorObjectOwned) -> String {
+        err.data()
+            .map(|data| data.get().to_string())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn solana_errors_are_classified() {
+        let unavailable =
+            solana_error(ClientErrorKind::Io(std::io::Error::other("connection refused")).into());
+        assert_eq!(unavailable.code(), RESOURCE_UNAVAILABLE_CODE);
+        assert_eq!(
+            data(&unavailable),
+            r#"{"source":"solana","reason":"solana_unavailable"}"#
+        );
+
+        let unhealthy = solana_error(
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                code: -32005,
+                message: "node is behind".to_string(),
+                data: RpcResponseErrorData::NodeUnhealthy {
+                    num_slots_behind: Some(100),
+                },
+            })
+            .into(),
+        );
+        assert_eq!(unhealthy.code(), RESOURCE_UNAVAILABLE_CODE);
+
+        let other = solana_error(ClientErrorKind::Custom("unexpected".to_string()).into());
+        assert_eq!(other.code(), INTERNAL_ERROR_CODE);
+        assert_eq!(
+            data(&other),
+            r#"{"source":"solana","reason":"internal_error"}"#
+        );
+    }
+
+    #[test]
+    fn transaction_errors_are_classified() {
+        let error =
+            |err: TransactionError| solana_error(ClientErrorKind::TransactionError(err).into());
+
+        let insufficient = error(TransactionError::InsufficientFundsForFee);
+        assert_eq!(insufficient.code(), RESOURCE_UNAVAILABLE_CODE);
+        assert_eq!(
+            data(&insufficient),
+            r#"{"source":"solana","reason":"payer_insufficient_lamports"}"#
+        );
+
+        assert_eq!(
+            error(TransactionError::BlockhashNotFound).code(),
+            RESOURCE_UNAVAILABLE_CODE
+        );
+        assert_eq!(
+            error(TransactionError::AlreadyProcessed).code(),
+            INVALID_INPUT_CODE
+        );
+        assert_eq!(
+            error(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(1)
+            ))
+            .code(),
+            TRANSACTION_REJECTED_CODE
+        );
+    }
+}